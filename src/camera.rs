@@ -1,6 +1,7 @@
 
 use crate::math::vec3::{Point3, Vec3};
 use crate::ray::Ray;
+use crate::sampler::Sampler;
 
 
 #[derive(Clone, Copy)]
@@ -34,7 +35,9 @@ pub struct Camera {
     view_forward: Vec3,
     view_right: Vec3,
     view_up: Vec3,
-    lens_radius: f64
+    lens_radius: f64,
+    viewport_height: f64,
+    pixel_spread: f64
 }
 
 impl Camera {
@@ -47,7 +50,9 @@ impl Camera {
             view_forward: Vec3::new(0.0, 0.0, 1.0),
             view_right: Vec3::new(1.0, 0.0, 0.0),
             view_up: Vec3::new(0.0, 1.0, 0.0),
-            lens_radius: 0.1
+            lens_radius: 0.1,
+            viewport_height: 2.0,
+            pixel_spread: 0.0
         }
     }
 
@@ -74,15 +79,23 @@ impl Camera {
         self.vertical = self.view_up * viewport_height * settings.focus_dist;
         self.lower_left = self.origin - self.horizontal / 2.0 - self.vertical / 2.0 - self.view_forward * settings.focus_dist;
         self.lens_radius = settings.aperture / 2.0;
+        self.viewport_height = viewport_height;
     }
 
-    pub fn get_ray(&self, u: f64, v: f64) -> Ray {
-        let rand_disk = Vec3::rand_in_unit_disk() * self.lens_radius;
+    // Angle one pixel covers, gives camera rays the cone used to pick texture detail.
+    pub fn set_image_height(&mut self, image_height: usize) {
+        self.pixel_spread = self.viewport_height / image_height.max(1) as f64;
+    }
+
+    pub fn get_ray(&self, u: f64, v: f64, sampler: &mut dyn Sampler) -> Ray {
+        let rand_disk = Vec3::sample_in_unit_disk(sampler.get_2d()) * self.lens_radius;
         let offset = self.view_right * rand_disk.x + self.view_up * rand_disk.y;
 
-        Ray::new(
+        Ray::new_with_cone(
             self.origin + offset,
-            self.lower_left + self.horizontal * u + self.vertical * v - self.origin - offset
+            self.lower_left + self.horizontal * u + self.vertical * v - self.origin - offset,
+            0.0,
+            self.pixel_spread
         )
     }
 }
@@ -0,0 +1,231 @@
+
+use std::path::PathBuf;
+
+use crate::threading::tile::TileOrder;
+use crate::display::{ToneMapper, TONE_MAPPERS};
+use crate::output::exr::ExrPixelType;
+use crate::denoise::DenoiseMode;
+use crate::sampler::{SamplerType, SAMPLERS};
+
+
+pub const USAGE: &str = "\
+usage: raytracer-rs [options] [scene file]
+
+options:
+    --scene <file>        scene description to render, same as the positional argument
+    --width <pixels>      image width (default 1280)
+    --height <pixels>     image height (default 720)
+    --samples <count>     samples per pixel, overrides the scene settings
+    --adaptive <error>    stop sampling pixels once their relative error is below this, 0 turns it off
+    --min-samples <count> samples every pixel gets before adaptive sampling may stop it (default 16)
+    --sampler <name>      independent, stratified, halton or sobol, overrides the scene settings
+    --seed <number>       seed of every random choice, the same seed gives the same image (default 0)
+    --threads <count>     number of render threads (default: all but two cores)
+    --tile-size <pixels>  side of the square tiles threads take work in, overrides the scene settings
+    --tile-order <order>  scanline, spiral or hilbert, overrides the scene settings
+    --exposure <stops>    brightens (or darkens when negative) the image, overrides the scene settings
+    --no-dither           quantize to 8 bits without dithering
+    --tone-mapper <name>  clamp, reinhard, reinhard_extended, aces, hable or agx, overrides the scene settings
+    --white-point <value> radiance shown as white by reinhard_extended and hable, overrides the scene settings
+    --denoise <mode>      off, final or progressive, overrides the scene settings
+    --output <file>       render without a window and write the image (.png, .ppm, .exr or .hdr)
+    --exr-type <type>     half or float channels in .exr files (default half)
+    --aovs                also write depth, normal, albedo, position, material and object id passes,
+                          as layers of an .exr file or as <name>.<pass>.<ext> next to other formats
+    --headless            render without a window, writes output.png unless --output is given
+    --help                print this message";
+
+pub struct CliOptions {
+    pub scene_path: Option<PathBuf>,
+    pub size: (usize, usize),
+    pub sample_count: Option<u32>,
+    pub adaptive_threshold: Option<f64>,
+    pub min_sample_count: Option<u32>,
+    pub sampler: Option<SamplerType>,
+    pub seed: Option<u64>,
+    pub thread_count: Option<usize>,
+    pub tile_size: Option<usize>,
+    pub tile_order: Option<TileOrder>,
+    pub exposure: Option<f64>,
+    pub no_dither: bool,
+    pub tone_mapper: Option<ToneMapper>,
+    pub white_point: Option<f64>,
+    pub denoise: Option<DenoiseMode>,
+    pub output_path: PathBuf,
+    pub exr_pixel_type: ExrPixelType,
+    pub write_aovs: bool,
+    pub is_headless: bool,
+    pub show_help: bool
+}
+
+impl CliOptions {
+    pub fn new_default() -> CliOptions {
+        CliOptions {
+            scene_path: None,
+            size: (1280, 720),
+            sample_count: None,
+            adaptive_threshold: None,
+            min_sample_count: None,
+            sampler: None,
+            seed: None,
+            thread_count: None,
+            tile_size: None,
+            tile_order: None,
+            exposure: None,
+            no_dither: false,
+            tone_mapper: None,
+            white_point: None,
+            denoise: None,
+            output_path: PathBuf::from("output.png"),
+            exr_pixel_type: ExrPixelType::Half,
+            write_aovs: false,
+            is_headless: false,
+            show_help: false
+        }
+    }
+
+    pub fn parse<I>(arguments: I) -> Result<CliOptions, String>
+        where I: IntoIterator<Item = String>
+    {
+        let mut options = CliOptions::new_default();
+        let mut arguments = arguments.into_iter();
+        while let Some(argument) = arguments.next() {
+            match argument.as_str() {
+                "--help" | "-h" => options.show_help = true,
+                "--headless" => options.is_headless = true,
+                "--scene" => {
+                    let path = expect_value(&argument, arguments.next())?;
+                    options.set_scene_path(path)?;
+                }
+                "--width" => options.size.0 = parse_positive(&argument, arguments.next())?,
+                "--height" => options.size.1 = parse_positive(&argument, arguments.next())?,
+                "--samples" => options.sample_count = Some(parse_positive(&argument, arguments.next())?),
+                "--adaptive" => {
+                    let threshold = parse_number(&argument, arguments.next())?;
+                    if threshold < 0.0 {
+                        return Err(format!("'{}' can not be negative", argument));
+                    }
+                    options.adaptive_threshold = Some(threshold);
+                }
+                "--min-samples" => options.min_sample_count = Some(parse_positive(&argument, arguments.next())?),
+                "--sampler" => {
+                    let value = expect_value(&argument, arguments.next())?;
+                    options.sampler = match SAMPLERS.iter().find(|(name, _)| *name == value) {
+                        Some((_, sampler)) => Some(*sampler),
+                        None => {
+                            let names: Vec<&str> = SAMPLERS.iter().map(|(name, _)| *name).collect();
+                            return Err(format!("'{}' expects one of {}, found '{}'", argument, names.join(", "), value));
+                        }
+                    };
+                }
+                "--seed" => {
+                    let value = expect_value(&argument, arguments.next())?;
+                    options.seed = match value.parse::<u64>() {
+                        Ok(seed) => Some(seed),
+                        Err(_) => return Err(format!("'{}' expects a whole number, found '{}'", argument, value))
+                    };
+                }
+                "--threads" => options.thread_count = Some(parse_positive(&argument, arguments.next())?),
+                "--tile-size" => options.tile_size = Some(parse_positive(&argument, arguments.next())?),
+                "--tile-order" => {
+                    let value = expect_value(&argument, arguments.next())?;
+                    options.tile_order = match value.as_str() {
+                        "scanline" => Some(TileOrder::Scanline),
+                        "spiral" => Some(TileOrder::Spiral),
+                        "hilbert" => Some(TileOrder::Hilbert),
+                        _ => {
+                            return Err(format!("'{}' expects scanline, spiral or hilbert, found '{}'", argument, value));
+                        }
+                    };
+                }
+                "--exposure" => options.exposure = Some(parse_number(&argument, arguments.next())?),
+                "--no-dither" => options.no_dither = true,
+                "--tone-mapper" => {
+                    let value = expect_value(&argument, arguments.next())?;
+                    options.tone_mapper = match TONE_MAPPERS.iter().find(|(name, _)| *name == value) {
+                        Some((_, tone_mapper)) => Some(*tone_mapper),
+                        None => {
+                            let names: Vec<&str> = TONE_MAPPERS.iter().map(|(name, _)| *name).collect();
+                            return Err(format!("'{}' expects one of {}, found '{}'", argument, names.join(", "), value));
+                        }
+                    };
+                }
+                "--white-point" => options.white_point = Some(parse_number(&argument, arguments.next())?),
+                "--exr-type" => {
+                    let value = expect_value(&argument, arguments.next())?;
+                    options.exr_pixel_type = match value.as_str() {
+                        "half" => ExrPixelType::Half,
+                        "float" => ExrPixelType::Float,
+                        _ => {
+                            return Err(format!("'{}' expects half or float, found '{}'", argument, value));
+                        }
+                    };
+                }
+                "--aovs" => options.write_aovs = true,
+                "--denoise" => {
+                    let value = expect_value(&argument, arguments.next())?;
+                    options.denoise = match value.as_str() {
+                        "off" => Some(DenoiseMode::Off),
+                        "final" => Some(DenoiseMode::Final),
+                        "progressive" => Some(DenoiseMode::Progressive),
+                        _ => {
+                            return Err(format!("'{}' expects off, final or progressive, found '{}'", argument, value));
+                        }
+                    };
+                }
+                "--output" => {
+                    options.output_path = PathBuf::from(expect_value(&argument, arguments.next())?);
+                    options.is_headless = true;
+                }
+                _ => {
+                    if argument.starts_with("--") {
+                        return Err(format!("unknown option '{}'", argument));
+                    }
+
+                    options.set_scene_path(argument)?;
+                }
+            }
+        }
+
+        if options.size.0 < 2 || options.size.1 < 2 {
+            return Err("the image has to be at least 2x2 pixels".to_string());
+        }
+
+        Ok(options)
+    }
+
+    // --scene and the positional argument name the same thing, a second scene is an error either way
+    fn set_scene_path(&mut self, path: String) -> Result<(), String> {
+        if self.scene_path.is_some() {
+            return Err(format!("unexpected scene '{}', only one scene can be rendered", path));
+        }
+
+        self.scene_path = Some(PathBuf::from(path));
+        Ok(())
+    }
+}
+
+fn expect_value(option: &str, value: Option<String>) -> Result<String, String> {
+    match value {
+        Some(value) => Ok(value),
+        None => Err(format!("'{}' needs a value", option))
+    }
+}
+
+fn parse_positive<T>(option: &str, value: Option<String>) -> Result<T, String>
+    where T: std::str::FromStr + PartialOrd + Default
+{
+    let value = expect_value(option, value)?;
+    match value.parse::<T>() {
+        Ok(number) if number > T::default() => Ok(number),
+        _ => Err(format!("'{}' expects a positive whole number, found '{}'", option, value))
+    }
+}
+
+fn parse_number(option: &str, value: Option<String>) -> Result<f64, String> {
+    let value = expect_value(option, value)?;
+    match value.parse::<f64>() {
+        Ok(number) if number.is_finite() => Ok(number),
+        _ => Err(format!("'{}' expects a number, found '{}'", option, value))
+    }
+}
@@ -0,0 +1,120 @@
+
+use crate::film::{Film, Aov, get_luminance};
+use crate::math::vec3::{Vec3, Color};
+
+
+// B3 spline taps of the a-trous wavelet, applied along x and y
+const KERNEL: [f64; 5] = [1.0 / 16.0, 1.0 / 4.0, 3.0 / 8.0, 1.0 / 4.0, 1.0 / 16.0];
+
+// how strictly the guides keep neighbours apart, larger is stricter
+const LUMINANCE_SIGMA: f64 = 4.0;
+const NORMAL_POWER: i32 = 64;
+const PLANE_SIGMA: f64 = 1.0;
+
+// albedo below this is not divided out, it would only blow up the noise
+const MIN_ALBEDO: f64 = 0.01;
+
+
+// When the denoiser runs.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum DenoiseMode {
+    Off,
+    // once the last sample is taken
+    Final,
+    // every few seconds while rendering and once at the end
+    Progressive
+}
+
+// Edge-avoiding a-trous wavelet filter (Dammertz et al. 2010) over the beauty of film, rows from the top.
+// Lighting is filtered apart from the albedo so textures stay sharp, and neighbours only count
+// when their normal, their distance from the tangent plane and their brightness fit the pixel.
+// Every iteration doubles the distance between the taps, so five iterations cover a 125 pixel wide area.
+pub fn denoise_film(film: &Film, iterations: u32) -> Vec<Color> {
+    let size = film.get_size();
+    let color = film.get_image(Aov::Beauty);
+    let albedo = film.get_image(Aov::Albedo);
+    let normal = film.get_image(Aov::Normal);
+    let position = film.get_image(Aov::Position);
+    let depth = film.get_image(Aov::Depth);
+
+    let demodulate = |value: f64, albedo: f64| if albedo > MIN_ALBEDO { value / albedo } else { value };
+    let mut lighting: Vec<Color> = color.iter().zip(albedo.iter())
+        .map(|(color, albedo)| Color::new(demodulate(color[0], albedo[0]), demodulate(color[1], albedo[1]), demodulate(color[2], albedo[2])))
+        .collect();
+
+    // the noise of the lighting is the noise of the color without the albedo
+    let mut variance: Vec<f64> = film.get_variance_image().iter().zip(albedo.iter())
+        .map(|(variance, albedo)| variance / get_luminance(albedo).max(MIN_ALBEDO).powi(2))
+        .collect();
+
+    // world size of one pixel at unit depth, measured along the width
+    let pixel_scale = 2.0 / size.0 as f64;
+
+    for iteration in 0 .. iterations {
+        let step = 1_i64 << iteration;
+        let mut next_lighting = lighting.clone();
+        let mut next_variance = variance.clone();
+
+        for y in 0 .. size.1 {
+            for x in 0 .. size.0 {
+                let index = y * size.0 + x;
+                let center_depth = depth[index][0];
+                let center_luminance = get_luminance(&lighting[index]);
+                let luminance_scale = LUMINANCE_SIGMA * variance[index].sqrt() + 1e-6;
+
+                let mut weight_sum = 0.0;
+                let mut lighting_sum = Color::new_default();
+                let mut variance_sum = 0.0;
+                for (tap_y, kernel_y) in KERNEL.iter().enumerate() {
+                    for (tap_x, kernel_x) in KERNEL.iter().enumerate() {
+                        let sample_x = x as i64 + (tap_x as i64 - 2) * step;
+                        let sample_y = y as i64 + (tap_y as i64 - 2) * step;
+                        if sample_x < 0 || sample_y < 0 || sample_x >= size.0 as i64 || sample_y >= size.1 as i64 {
+                            continue;
+                        }
+                        let sample_index = sample_y as usize * size.0 + sample_x as usize;
+
+                        // the sky only mixes with the sky
+                        let sample_depth = depth[sample_index][0];
+                        let geometry_weight = match (center_depth > 0.0, sample_depth > 0.0) {
+                            (false, false) => 1.0,
+                            (true, true) => {
+                                let normal_weight = Vec3::dot(&normal[index], &normal[sample_index]).max(0.0).powi(NORMAL_POWER);
+                                let plane_distance = Vec3::dot(&normal[index], &(position[sample_index] - position[index])).abs();
+                                let allowed_distance = PLANE_SIGMA * center_depth * pixel_scale * step as f64;
+                                normal_weight * (-plane_distance / allowed_distance.max(1e-9)).exp()
+                            }
+                            _ => 0.0
+                        };
+                        if geometry_weight <= 0.0 {
+                            continue;
+                        }
+
+                        let luminance_difference = (get_luminance(&lighting[sample_index]) - center_luminance).abs();
+                        let luminance_weight = (-luminance_difference / luminance_scale).exp();
+
+                        let weight = kernel_x * kernel_y * geometry_weight * luminance_weight;
+                        weight_sum += weight;
+                        lighting_sum += lighting[sample_index] * weight;
+                        variance_sum += weight * weight * variance[sample_index];
+                    }
+                }
+
+                // averaged normals on silhouettes can even reject the center tap
+                if weight_sum <= 0.0 {
+                    continue;
+                }
+                next_lighting[index] = lighting_sum / weight_sum;
+                next_variance[index] = variance_sum / (weight_sum * weight_sum);
+            }
+        }
+
+        lighting = next_lighting;
+        variance = next_variance;
+    }
+
+    let remodulate = |value: f64, albedo: f64| if albedo > MIN_ALBEDO { value * albedo } else { value };
+    lighting.iter().zip(albedo.iter())
+        .map(|(lighting, albedo)| Color::new(remodulate(lighting[0], albedo[0]), remodulate(lighting[1], albedo[1]), remodulate(lighting[2], albedo[2])))
+        .collect()
+}
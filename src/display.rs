@@ -0,0 +1,241 @@
+
+use crate::math::vec3::{Vec3, Color};
+use crate::film::{Aov, get_luminance};
+use crate::math::hash::mix_bits;
+
+
+// Compresses radiance above 1.0 into the displayable range instead of clipping it.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum ToneMapper {
+    // clips every channel at 1.0
+    Clamp,
+    // L / (1 + L) on luminance, never quite reaches white
+    Reinhard,
+    // Reinhard that maps the white point to exactly 1.0
+    ReinhardExtended,
+    // Narkowicz's fit of the ACES filmic curve
+    Aces,
+    // Hable's Uncharted 2 filmic curve, divided by the curve's value at the white point so that maps to 1.0
+    Hable,
+    // AgX style log encoding and sigmoid, desaturates bright colors towards white
+    Agx
+}
+
+// Names used by scene files, the command line and the window title, in the order the viewer cycles through them.
+pub const TONE_MAPPERS: [(&str, ToneMapper); 6] = [
+    ("clamp", ToneMapper::Clamp),
+    ("reinhard", ToneMapper::Reinhard),
+    ("reinhard_extended", ToneMapper::ReinhardExtended),
+    ("aces", ToneMapper::Aces),
+    ("hable", ToneMapper::Hable),
+    ("agx", ToneMapper::Agx)
+];
+
+impl ToneMapper {
+    pub fn get_name(&self) -> &'static str {
+        match TONE_MAPPERS.iter().find(|(_, tone_mapper)| tone_mapper == self) {
+            Some((name, _)) => name,
+            None => "clamp"
+        }
+    }
+
+    pub fn get_next(&self) -> ToneMapper {
+        let index = TONE_MAPPERS.iter().position(|(_, tone_mapper)| tone_mapper == self).unwrap_or(0);
+        TONE_MAPPERS[(index + 1) % TONE_MAPPERS.len()].1
+    }
+
+    // Linear radiance in, linear display value in about [0, 1] out.
+    // white_point is the radiance that should end up white, only the Reinhard extended and Hable curves use it.
+    pub fn apply(&self, color: &Color, white_point: f64) -> Color {
+        match self {
+            ToneMapper::Clamp => *color,
+            ToneMapper::Reinhard => scale_luminance(color, |luminance| luminance / (1.0 + luminance)),
+            ToneMapper::ReinhardExtended => {
+                let white_squared = white_point * white_point;
+                scale_luminance(color, |luminance| luminance * (1.0 + luminance / white_squared) / (1.0 + luminance))
+            }
+            ToneMapper::Aces => map_channels(color, |value| {
+                let value = value * 0.6;
+                (value * (2.51 * value + 0.03)) / (value * (2.43 * value + 0.59) + 0.14)
+            }),
+            ToneMapper::Hable => {
+                // the usual exposure bias of the curve, applied to the white point as well
+                let exposure_bias = 2.0;
+                let white_scale = 1.0 / hable_curve(white_point * exposure_bias);
+                map_channels(color, |value| hable_curve(value * exposure_bias) * white_scale)
+            }
+            ToneMapper::Agx => apply_agx(color)
+        }
+    }
+}
+
+// Turns linear radiance from the film into 8-bit sRGB for the window and the output file.
+// The film itself is never changed, so the same render can be shown with other settings.
+#[derive(Clone, Copy)]
+pub struct DisplayTransform {
+    // in stops, every +1 doubles the brightness
+    pub exposure: f64,
+    // adds less than one step of noise before quantizing so smooth gradients do not band
+    pub dither: bool,
+    pub tone_mapper: ToneMapper,
+    pub white_point: f64
+}
+
+impl DisplayTransform {
+    pub fn new_default() -> DisplayTransform {
+        DisplayTransform {
+            exposure: 0.0,
+            dither: true,
+            tone_mapper: ToneMapper::Clamp,
+            white_point: 4.0
+        }
+    }
+
+    pub fn new(exposure: f64, dither: bool, tone_mapper: ToneMapper, white_point: f64) -> DisplayTransform {
+        DisplayTransform { exposure, dither, tone_mapper, white_point }
+    }
+
+    // position only seeds the dither, the same pixel always gets the same offset
+    pub fn encode(&self, color: &Color, position: (usize, usize)) -> [u8; 3] {
+        let scale = 2.0_f64.powf(self.exposure);
+        let mapped = self.tone_mapper.apply(&(*color * scale), self.white_point.max(1e-3));
+        self.quantize(&map_channels(&mapped, |value| linear_to_srgb(value.min(1.0))), position)
+    }
+
+    // Auxiliary passes are shown as false color without exposure or tone mapping.
+    // range is the largest value of the pass in the film: the farthest first hit for depth and position,
+    // the most samples any pixel got for the sample count.
+    pub fn encode_aov(&self, aov: Aov, value: &Color, range: f64, position: (usize, usize)) -> [u8; 3] {
+        let scale = 1.0 / range.max(1e-6);
+        let preview = match aov {
+            Aov::Beauty => return self.encode(value, position),
+            // near is bright, nothing hit stays black
+            Aov::Depth => match value[0] > 0.0 {
+                true => Color::new(1.0, 1.0, 1.0) * (1.0 - value[0] * scale * 0.9),
+                false => Color::new_default()
+            },
+            Aov::Normal => Color::new(0.5, 0.5, 0.5) + *value * 0.5,
+            Aov::Position => Color::new(0.5, 0.5, 0.5) + *value * (0.5 * scale),
+            Aov::Albedo => map_channels(value, |channel| linear_to_srgb(channel.min(1.0))),
+            Aov::MaterialId | Aov::ObjectId => get_id_color(value[0].round() as u64),
+            Aov::SampleCount => get_heat_color(value[0] * scale)
+        };
+        self.quantize(&preview, position)
+    }
+
+    // display encoded values in [0, 1] to bytes
+    fn quantize(&self, color: &Color, position: (usize, usize)) -> [u8; 3] {
+        let offset = match self.dither {
+            true => get_dither_offset(position),
+            false => 0.0
+        };
+
+        let mut encoded = [0; 3];
+        for (i, channel) in encoded.iter_mut().enumerate() {
+            let value = match color[i] {
+                value if value.is_nan() => 0.0,
+                value => value.clamp(0.0, 1.0)
+            };
+            *channel = (value * 255.0 + 0.5 + offset).clamp(0.0, 255.0) as u8;
+        }
+        encoded
+    }
+}
+
+pub fn linear_to_srgb(value: f64) -> f64 {
+    if value <= 0.0031308 {
+        value * 12.92
+    } else {
+        1.055 * value.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+// Scales the whole color so its luminance becomes curve(luminance), keeps the hue.
+fn scale_luminance<F: Fn(f64) -> f64>(color: &Color, curve: F) -> Color {
+    let luminance = get_luminance(color);
+    if luminance <= 0.0 {
+        return Color::new_default();
+    }
+    *color * (curve(luminance) / luminance)
+}
+
+fn map_channels<F: Fn(f64) -> f64>(color: &Color, curve: F) -> Color {
+    Color::new(curve(color[0].max(0.0)), curve(color[1].max(0.0)), curve(color[2].max(0.0)))
+}
+
+fn hable_curve(value: f64) -> f64 {
+    let (a, b, c, d, e, f) = (0.15, 0.50, 0.10, 0.20, 0.02, 0.30);
+    ((value * (a * value + c * b) + d * e) / (value * (a * value + b) + d * f)) - e / f
+}
+
+// Rows of the AgX inset matrix and its inverse, for linear Rec.709 input.
+const AGX_INSET: [[f64; 3]; 3] = [
+    [0.842479062253094, 0.0784335999999992, 0.0792237451477643],
+    [0.0423282422610123, 0.878468636469772, 0.0791661274605434],
+    [0.0423756549057051, 0.0784336, 0.879142973793104]
+];
+const AGX_OUTSET: [[f64; 3]; 3] = [
+    [1.19687900512017, -0.0980208811401368, -0.0990297440797205],
+    [-0.0528968517574562, 1.15190312990417, -0.0989611768448433],
+    [-0.0529716355144438, -0.0980434501171241, 1.15107367264116]
+];
+const AGX_MIN_EV: f64 = -12.47393;
+const AGX_MAX_EV: f64 = 4.026069;
+
+fn apply_agx(color: &Color) -> Color {
+    let inset = multiply_matrix(&AGX_INSET, color);
+
+    // log2 encoding over a fixed range of stops, then a sigmoid fitted to the AgX base contrast
+    let sigmoid = map_channels(&inset, |value| {
+        let encoded = ((value.max(1e-10).log2() - AGX_MIN_EV) / (AGX_MAX_EV - AGX_MIN_EV)).clamp(0.0, 1.0);
+        let x2 = encoded * encoded;
+        let x4 = x2 * x2;
+        15.5 * x4 * x2 - 40.14 * x4 * encoded + 31.96 * x4 - 6.868 * x2 * encoded + 0.4298 * x2 + 0.1191 * encoded - 0.00232
+    });
+
+    // the sigmoid output is display encoded with a 2.2 gamma, decode it back to linear
+    let outset = multiply_matrix(&AGX_OUTSET, &sigmoid);
+    map_channels(&outset, |value| value.powf(2.2))
+}
+
+fn multiply_matrix(matrix: &[[f64; 3]; 3], vector: &Vec3) -> Vec3 {
+    let row = |index: usize| matrix[index][0] * vector[0] + matrix[index][1] * vector[1] + matrix[index][2] * vector[2];
+    Vec3::new(row(0), row(1), row(2))
+}
+
+// Blue through cyan, green and yellow to red as t goes from 0 to 1.
+fn get_heat_color(t: f64) -> Color {
+    let stops = [
+        Color::new(0.0, 0.0, 1.0),
+        Color::new(0.0, 1.0, 1.0),
+        Color::new(0.0, 1.0, 0.0),
+        Color::new(1.0, 1.0, 0.0),
+        Color::new(1.0, 0.0, 0.0)
+    ];
+    let position = t.clamp(0.0, 1.0) * (stops.len() - 1) as f64;
+    let index = (position as usize).min(stops.len() - 2);
+    let fraction = position - index as f64;
+    stops[index] * (1.0 - fraction) + stops[index + 1] * fraction
+}
+
+// A bright color that stays the same for every id, zero is black.
+fn get_id_color(id: u64) -> Color {
+    if id == 0 {
+        return Color::new_default();
+    }
+
+    let hash = mix_bits(id);
+    let to_channel = |bits: u64| 0.2 + 0.8 * (bits & 0xff) as f64 / 255.0;
+    Color::new(to_channel(hash), to_channel(hash >> 8), to_channel(hash >> 16))
+}
+
+// Triangular noise in (-1, 1) steps, the difference of two uniform values hashed from the pixel.
+fn get_dither_offset(position: (usize, usize)) -> f64 {
+    let hash = hash_position(position);
+    let to_fraction = |bits: u64| (bits & 0xffff_ffff) as f64 / (1u64 << 32) as f64;
+    to_fraction(hash) - to_fraction(hash >> 32)
+}
+
+fn hash_position(position: (usize, usize)) -> u64 {
+    mix_bits((position.0 as u64) << 32 | position.1 as u64)
+}
@@ -0,0 +1,305 @@
+
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::math::vec3::{Vec3, Point3, Color};
+use crate::threading::tile::Tile;
+
+
+// Passes the film keeps for every pixel, beauty is the rendered image and the rest describe the first hit.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Aov {
+    Beauty,
+    // distance from the camera along the ray, zero where nothing was hit
+    Depth,
+    // world space normal facing the camera
+    Normal,
+    Albedo,
+    Position,
+    // ids are taken from the first sample instead of averaged, zero where nothing was hit
+    MaterialId,
+    ObjectId,
+    // how many samples the pixel got, differs between pixels with adaptive sampling
+    SampleCount
+}
+
+// Names used by file names, EXR layers and the window title, in the order the viewer cycles through them.
+pub const AOVS: [(&str, Aov); 8] = [
+    ("beauty", Aov::Beauty),
+    ("depth", Aov::Depth),
+    ("normal", Aov::Normal),
+    ("albedo", Aov::Albedo),
+    ("position", Aov::Position),
+    ("material_id", Aov::MaterialId),
+    ("object_id", Aov::ObjectId),
+    ("sample_count", Aov::SampleCount)
+];
+
+// passes taken from the first hit, stored next to each other for every pixel
+const AOV_LAYERS: [Aov; 6] = [Aov::Depth, Aov::Normal, Aov::Albedo, Aov::Position, Aov::MaterialId, Aov::ObjectId];
+
+impl Aov {
+    pub fn get_name(&self) -> &'static str {
+        match AOVS.iter().find(|(_, aov)| aov == self) {
+            Some((name, _)) => name,
+            None => "beauty"
+        }
+    }
+
+    pub fn get_next(&self) -> Aov {
+        let index = AOVS.iter().position(|(_, aov)| aov == self).unwrap_or(0);
+        AOVS[(index + 1) % AOVS.len()].1
+    }
+
+    // one value per pixel instead of a color
+    pub fn is_scalar(&self) -> bool {
+        matches!(self, Aov::Depth | Aov::MaterialId | Aov::ObjectId | Aov::SampleCount)
+    }
+
+    fn get_layer_index(&self) -> Option<usize> {
+        AOV_LAYERS.iter().position(|aov| aov == self)
+    }
+
+    fn is_averaged(&self) -> bool {
+        !matches!(self, Aov::MaterialId | Aov::ObjectId)
+    }
+}
+
+// Everything one camera ray found for its pixel.
+#[derive(Clone, Copy)]
+pub struct FilmSample {
+    pub color: Color,
+    pub depth: f64,
+    pub normal: Vec3,
+    pub albedo: Color,
+    pub position: Point3,
+    pub material_id: u32,
+    pub object_id: u32
+}
+
+impl FilmSample {
+    // a ray that left the scene, only the color and albedo see the sky
+    pub fn new_background(sky_color: Color) -> FilmSample {
+        FilmSample {
+            color: sky_color,
+            depth: 0.0,
+            normal: Vec3::new_default(),
+            albedo: sky_color,
+            position: Point3::new_default(),
+            material_id: 0,
+            object_id: 0
+        }
+    }
+
+    fn get_aov(&self, aov: Aov) -> Color {
+        match aov {
+            Aov::Beauty => self.color,
+            Aov::Depth => Color::new(self.depth, self.depth, self.depth),
+            Aov::Normal => self.normal,
+            Aov::Albedo => self.albedo,
+            Aov::Position => self.position,
+            Aov::MaterialId => Color::new(self.material_id as f64, self.material_id as f64, self.material_id as f64),
+            Aov::ObjectId => Color::new(self.object_id as f64, self.object_id as f64, self.object_id as f64),
+            Aov::SampleCount => Color::new(1.0, 1.0, 1.0)
+        }
+    }
+}
+
+struct FilmData {
+    sums: Vec<Color>,
+    // for the variance of every pixel
+    luminance_square_sums: Vec<f64>,
+    // the auxiliary passes are only for compositing, single precision is plenty
+    aov_sums: Vec<[f32; 3]>,
+    sample_counts: Vec<u32>,
+    dirty_tiles: Vec<Tile>,
+    max_depth: f64,
+    max_sample_count: u32
+}
+
+// Linear radiance shared by all workers, each finished tile is added under one short lock.
+// The display side only converts the tiles that changed since it last looked.
+pub struct Film {
+    size: (usize, usize),
+    data: Mutex<FilmData>,
+    finished_jobs: AtomicUsize
+}
+
+impl Film {
+    pub fn new(size: (usize, usize)) -> Film {
+        let pixel_count = size.0 * size.1;
+        Film {
+            size,
+            data: Mutex::new(FilmData {
+                sums: vec![Color::new_default(); pixel_count],
+                luminance_square_sums: vec![0.0; pixel_count],
+                aov_sums: vec![[0.0; 3]; pixel_count * AOV_LAYERS.len()],
+                sample_counts: vec![0; pixel_count],
+                dirty_tiles: Vec::new(),
+                max_depth: 0.0,
+                max_sample_count: 0
+            }),
+            finished_jobs: AtomicUsize::new(0)
+        }
+    }
+
+    // samples holds one entry per pixel of tile, row by row from the bottom, None for pixels that were skipped
+    pub fn add_tile(&self, tile: &Tile, samples: &[Option<FilmSample>]) {
+        {
+            let mut data = self.data.lock().unwrap();
+            for (index, sample) in samples.iter().enumerate() {
+                let sample = match sample {
+                    Some(sample) => sample,
+                    None => continue
+                };
+
+                let pixel_index = self.get_pixel_index(tile, index);
+                let is_first_sample = data.sample_counts[pixel_index] == 0;
+                data.sums[pixel_index] += sample.color;
+                data.luminance_square_sums[pixel_index] += get_luminance(&sample.color).powi(2);
+                data.sample_counts[pixel_index] += 1;
+                data.max_depth = data.max_depth.max(sample.depth);
+                data.max_sample_count = data.max_sample_count.max(data.sample_counts[pixel_index]);
+
+                for aov in AOV_LAYERS.iter() {
+                    if !aov.is_averaged() && !is_first_sample {
+                        continue;
+                    }
+
+                    let value = sample.get_aov(*aov);
+                    let sum = &mut data.aov_sums[Film::get_aov_index(pixel_index, *aov)];
+                    for (i, channel) in sum.iter_mut().enumerate() {
+                        *channel += value[i] as f32;
+                    }
+                }
+            }
+
+            // a tile is only listed once however many passes finished since the last look
+            let is_listed = data.dirty_tiles.iter().any(|dirty_tile| dirty_tile.x == tile.x && dirty_tile.y == tile.y);
+            if !is_listed && samples.iter().any(|sample| sample.is_some()) {
+                data.dirty_tiles.push(*tile);
+            }
+        }
+
+        self.finished_jobs.fetch_add(1, Ordering::Release);
+    }
+
+    pub fn take_dirty_tiles(&self) -> Vec<Tile> {
+        std::mem::take(&mut self.data.lock().unwrap().dirty_tiles)
+    }
+
+    // Average of aov for every pixel of tile, in the same order add_tile takes them.
+    pub fn get_tile_average(&self, tile: &Tile, aov: Aov) -> Vec<Color> {
+        let data = self.data.lock().unwrap();
+        (0 .. tile.get_pixel_count()).map(|index| {
+            Film::get_average(&data, self.get_pixel_index(tile, index), aov)
+        }).collect()
+    }
+
+    // Average of aov over the whole film with rows from the top, the order image files use.
+    pub fn get_image(&self, aov: Aov) -> Vec<Color> {
+        let data = self.data.lock().unwrap();
+        let mut image = Vec::with_capacity(self.size.0 * self.size.1);
+        for y in (0 .. self.size.1).rev() {
+            for x in 0 .. self.size.0 {
+                image.push(Film::get_average(&data, self.size.0 * y + x, aov));
+            }
+        }
+        image
+    }
+
+    // Variance of the luminance average of every pixel with rows from the top, it shrinks as samples are added.
+    pub fn get_variance_image(&self) -> Vec<f64> {
+        let data = self.data.lock().unwrap();
+        let mut image = Vec::with_capacity(self.size.0 * self.size.1);
+        for y in (0 .. self.size.1).rev() {
+            for x in 0 .. self.size.0 {
+                image.push(Film::get_luminance_variance(&data, self.size.0 * y + x).1);
+            }
+        }
+        image
+    }
+
+    // Which pixels of tile need no more samples: at least min_sample_count were taken and the standard error
+    // of the luminance is below threshold relative to the luminance, in the same order add_tile takes them.
+    pub fn get_converged_pixels(&self, tile: &Tile, threshold: f64, min_sample_count: u32) -> Vec<bool> {
+        let data = self.data.lock().unwrap();
+        (0 .. tile.get_pixel_count()).map(|index| {
+            let pixel_index = self.get_pixel_index(tile, index);
+            if data.sample_counts[pixel_index] < min_sample_count.max(2) {
+                return false;
+            }
+
+            let (mean, variance) = Film::get_luminance_variance(&data, pixel_index);
+            variance.sqrt() <= threshold * mean.max(1e-3)
+        }).collect()
+    }
+
+    pub fn get_max_sample_count(&self) -> u32 {
+        self.data.lock().unwrap().max_sample_count
+    }
+
+    // average of all pixels, with adaptive sampling it is below the sample count
+    pub fn get_average_sample_count(&self) -> f64 {
+        let data = self.data.lock().unwrap();
+        let sample_sum: u64 = data.sample_counts.iter().map(|sample_count| *sample_count as u64).sum();
+        sample_sum as f64 / data.sample_counts.len().max(1) as f64
+    }
+
+    // farthest first hit so far, lets the depth pass be shown without knowing the scale of the scene
+    pub fn get_max_depth(&self) -> f64 {
+        self.data.lock().unwrap().max_depth
+    }
+
+    pub fn get_finished_jobs(&self) -> usize {
+        self.finished_jobs.load(Ordering::Acquire)
+    }
+
+    pub fn get_size(&self) -> (usize, usize) {
+        self.size
+    }
+
+    fn get_pixel_index(&self, tile: &Tile, index_in_tile: usize) -> usize {
+        let x = tile.x + index_in_tile % tile.width;
+        let y = tile.y + index_in_tile / tile.width;
+        self.size.0 * y + x
+    }
+
+    fn get_aov_index(pixel_index: usize, aov: Aov) -> usize {
+        pixel_index * AOV_LAYERS.len() + aov.get_layer_index().unwrap_or(0)
+    }
+
+    // Mean and variance of the mean of the luminance of a pixel.
+    // Pixels with less than two samples are assumed to be as noisy as they are bright.
+    fn get_luminance_variance(data: &FilmData, pixel_index: usize) -> (f64, f64) {
+        let sample_count = data.sample_counts[pixel_index] as f64;
+        let mean = get_luminance(&data.sums[pixel_index]) / sample_count.max(1.0);
+        if sample_count < 2.0 {
+            return (mean, mean * mean);
+        }
+
+        let sample_variance = (data.luminance_square_sums[pixel_index] - sample_count * mean * mean) / (sample_count - 1.0);
+        (mean, sample_variance.max(0.0) / sample_count)
+    }
+
+    fn get_average(data: &FilmData, pixel_index: usize, aov: Aov) -> Color {
+        let sample_count = data.sample_counts[pixel_index];
+        if sample_count == 0 {
+            return Color::new_default();
+        }
+
+        match aov {
+            Aov::Beauty => data.sums[pixel_index] / sample_count as f64,
+            Aov::SampleCount => Color::new(sample_count as f64, sample_count as f64, sample_count as f64),
+            aov => {
+                let sum = data.aov_sums[Film::get_aov_index(pixel_index, aov)];
+                let divisor = if aov.is_averaged() { sample_count as f64 } else { 1.0 };
+                Color::new(sum[0] as f64, sum[1] as f64, sum[2] as f64) / divisor
+            }
+        }
+    }
+}
+
+pub fn get_luminance(color: &Color) -> f64 {
+    0.2126 * color[0] + 0.7152 * color[1] + 0.0722 * color[2]
+}
@@ -0,0 +1,93 @@
+
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+use crate::cli::CliOptions;
+use crate::output::{ImageLayer, write_image, is_supported_format, is_layered_format};
+use crate::raytracer::{RayTracer, RayTracerState};
+use crate::film::{Aov, AOVS};
+
+
+// Renders to completion on the calling thread and writes the image, for machines without a display.
+pub fn run_headless(mut raytracer: RayTracer, options: &CliOptions) -> Result<(), String> {
+    if !is_supported_format(&options.output_path) {
+        return Err(format!("{}: unsupported image format, use .png, .ppm, .exr or .hdr", options.output_path.display()));
+    }
+
+    let size = raytracer.get_buffer_size();
+    println!("rendering {width}x{height}, {samples} samples per pixel",
+        width = size.0,
+        height = size.1,
+        samples = raytracer.get_settings().sample_count);
+
+    let start_time = Instant::now();
+    raytracer.run();
+    while let RayTracerState::Working = raytracer.get_raytracer_state() {
+        raytracer.tick();
+    }
+
+    if let RayTracerState::Failed = raytracer.get_raytracer_state() {
+        return Err("a render thread panicked, no image was written".to_string());
+    }
+
+    println!("rendered in {seconds:.2}s", seconds = start_time.elapsed().as_secs_f64());
+
+    // the main image is the denoised one when the denoiser ran, the raw beauty is kept next to it
+    let film = raytracer.get_film();
+    let is_exr = is_layered_format(&options.output_path);
+    let raw_image = film.get_image(Aov::Beauty);
+    let denoised_image = raytracer.get_denoised_image().cloned();
+    let mut layers = Vec::new();
+    match &denoised_image {
+        Some(denoised_image) => {
+            layers.push(ImageLayer::new("", denoised_image.clone()));
+            if is_exr {
+                layers.push(ImageLayer::new("raw", raw_image.clone()));
+            }
+        }
+        None => layers.push(ImageLayer::new("", raw_image.clone()))
+    }
+    if options.write_aovs && is_exr {
+        for (name, aov) in AOVS.iter().skip(1) {
+            layers.push(match aov.is_scalar() {
+                true => ImageLayer::new_scalar(name, film.get_image(*aov)),
+                false => ImageLayer::new(name, film.get_image(*aov))
+            });
+        }
+    }
+
+    write_image(&options.output_path, raytracer.consume_buffer(), &layers, options.exr_pixel_type).map_err(|error| error.to_string())?;
+    println!("wrote {path}", path = options.output_path.display());
+
+    // every other format gets one file per pass next to the main image
+    if is_exr {
+        return Ok(());
+    }
+
+    if denoised_image.is_some() {
+        let path = get_pass_path(&options.output_path, "raw");
+        raytracer.set_show_denoised(false);
+        let layers = vec![ImageLayer::new("raw", raw_image)];
+        write_image(&path, raytracer.consume_buffer(), &layers, options.exr_pixel_type).map_err(|error| error.to_string())?;
+        println!("wrote {path}", path = path.display());
+    }
+
+    if options.write_aovs {
+        for (name, aov) in AOVS.iter().skip(1) {
+            let path = get_pass_path(&options.output_path, name);
+            raytracer.set_aov(*aov);
+            let layers = vec![ImageLayer::new(name, film.get_image(*aov))];
+            write_image(&path, raytracer.consume_buffer(), &layers, options.exr_pixel_type).map_err(|error| error.to_string())?;
+            println!("wrote {path}", path = path.display());
+        }
+    }
+
+    Ok(())
+}
+
+// render.png becomes render.depth.png
+fn get_pass_path(path: &Path, pass_name: &str) -> PathBuf {
+    let stem = path.file_stem().map(|stem| stem.to_string_lossy().to_string()).unwrap_or_default();
+    let extension = path.extension().map(|extension| extension.to_string_lossy().to_string()).unwrap_or_default();
+    path.with_file_name(format!("{}.{}.{}", stem, pass_name, extension))
+}
@@ -0,0 +1,88 @@
+
+pub mod obj;
+pub mod mtl;
+
+#[cfg(test)]
+mod tests;
+
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+
+pub enum ImportError {
+    Io {
+        path: PathBuf,
+        error: std::io::Error
+    },
+    Parse {
+        path: PathBuf,
+        line: usize,
+        message: String
+    },
+    Image {
+        path: PathBuf,
+        message: String
+    }
+}
+
+impl ImportError {
+    pub fn new_parse(path: &Path, line: usize, message: &str) -> ImportError {
+        ImportError::Parse {
+            path: path.to_path_buf(),
+            line,
+            message: message.to_string()
+        }
+    }
+}
+
+impl fmt::Display for ImportError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ImportError::Io { path, error } => {
+                write!(f, "{path}: {error}", path = path.display(), error = error)
+            }
+            ImportError::Parse { path, line, message } => {
+                write!(f, "{path}:{line}: {message}", path = path.display(), line = line, message = message)
+            }
+            ImportError::Image { path, message } => {
+                write!(f, "{path}: {message}", path = path.display(), message = message)
+            }
+        }
+    }
+}
+
+impl fmt::Debug for ImportError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(self, f)
+    }
+}
+
+pub fn read_source(path: &Path) -> Result<String, ImportError> {
+    std::fs::read_to_string(path).map_err(|error| ImportError::Io { path: path.to_path_buf(), error })
+}
+
+// Parses the numeric arguments of a statement, at least min_count and at most max_count of them.
+pub fn parse_numbers(path: &Path, line: usize, keyword: &str, arguments: &[&str], min_count: usize, max_count: usize) -> Result<Vec<f64>, ImportError> {
+    if arguments.len() < min_count || arguments.len() > max_count {
+        let expected = if min_count == max_count {
+            format!("{}", min_count)
+        } else {
+            format!("{} to {}", min_count, max_count)
+        };
+        let message = format!("'{}' expects {} numbers, found {}", keyword, expected, arguments.len());
+        return Err(ImportError::new_parse(path, line, &message));
+    }
+
+    let mut numbers = Vec::with_capacity(arguments.len());
+    for argument in arguments {
+        match argument.parse::<f64>() {
+            Ok(number) if number.is_finite() => numbers.push(number),
+            _ => {
+                let message = format!("invalid number '{}' in '{}' statement", argument, keyword);
+                return Err(ImportError::new_parse(path, line, &message));
+            }
+        }
+    }
+
+    Ok(numbers)
+}
@@ -0,0 +1,209 @@
+
+use std::collections::HashMap;
+use std::path::Path;
+
+use crate::import::{ImportError, read_source, parse_numbers};
+use crate::material::Material;
+use crate::material::lambertian::Lambertian;
+use crate::material::metal::Metal;
+use crate::material::dielectric::Dielectric;
+use crate::material::diffuse_light::DiffuseLight;
+use crate::math::vec3::Color;
+use crate::texture::Texture;
+use crate::texture::solid_color::SolidColor;
+use crate::texture::image_texture::ImageTexture;
+
+
+#[derive(Clone)]
+pub struct MtlMaterial {
+    pub name: String,
+    pub diffuse: Color,
+    pub specular: Color,
+    pub emission: Color,
+    pub specular_exponent: f64,
+    pub refraction_index: f64,
+    pub dissolve: f64,
+    pub illumination: u32,
+    pub metallic: Option<f64>,
+    pub roughness: Option<f64>,
+    pub diffuse_map: Option<String>,
+    pub diffuse_texture: Option<ImageTexture>
+}
+
+impl MtlMaterial {
+    pub fn new_default() -> MtlMaterial {
+        MtlMaterial::new("default")
+    }
+
+    pub fn new(name: &str) -> MtlMaterial {
+        MtlMaterial {
+            name: name.to_string(),
+            diffuse: Color::new(0.8, 0.8, 0.8),
+            specular: Color::new(0.0, 0.0, 0.0),
+            emission: Color::new(0.0, 0.0, 0.0),
+            specular_exponent: 0.0,
+            refraction_index: 1.5,
+            dissolve: 1.0,
+            illumination: 2,
+            metallic: None,
+            roughness: None,
+            diffuse_map: None,
+            diffuse_texture: None
+        }
+    }
+
+    // Maps the Phong style description onto the closest material the renderer has.
+    pub fn to_material(&self) -> Box<dyn Material> {
+        if !self.emission.is_near_zero() {
+            return Box::new(DiffuseLight::new(self.emission));
+        }
+
+        let is_transparent = self.dissolve < 1.0 || matches!(self.illumination, 4 | 6 | 7 | 9);
+        if is_transparent {
+            return Box::new(Dielectric::new(self.refraction_index));
+        }
+
+        let is_metal = match self.metallic {
+            Some(metallic) => metallic >= 0.5,
+            None => self.illumination == 3 || self.illumination == 5
+        };
+        if is_metal {
+            // PBR extension materials keep the base color in Kd, classic ones in Ks
+            let albedo: Box<dyn Texture> = if self.metallic.is_some() {
+                self.get_diffuse_texture()
+            } else {
+                Box::new(SolidColor::new(self.specular))
+            };
+            let fuzziness = match self.roughness {
+                Some(roughness) => roughness,
+                None => (2.0 / (self.specular_exponent + 2.0)).sqrt()
+            }.clamp(0.0, 1.0);
+            let fuzziness = Box::new(SolidColor::new(Color::new(fuzziness, fuzziness, fuzziness)));
+            return Box::new(Metal::new_with_textures(albedo, fuzziness));
+        }
+
+        Box::new(Lambertian::new_with_texture(self.get_diffuse_texture()))
+    }
+
+    // map_Kd replaces Kd, exporters tend to leave a placeholder color next to the map
+    fn get_diffuse_texture(&self) -> Box<dyn Texture> {
+        match &self.diffuse_texture {
+            Some(texture) => Box::new(texture.clone()),
+            None => Box::new(SolidColor::new(self.diffuse))
+        }
+    }
+}
+
+pub fn load_mtl(path: &Path) -> Result<HashMap<String, MtlMaterial>, ImportError> {
+    let source = read_source(path)?;
+    parse_mtl(path, &source)
+}
+
+pub fn parse_mtl(path: &Path, source: &str) -> Result<HashMap<String, MtlMaterial>, ImportError> {
+    let mut materials: HashMap<String, MtlMaterial> = HashMap::new();
+    let mut current: Option<MtlMaterial> = None;
+
+    for (line_index, raw_line) in source.lines().enumerate() {
+        let line_number = line_index + 1;
+        let line = match raw_line.find('#') {
+            Some(comment_start) => &raw_line[.. comment_start],
+            None => raw_line
+        };
+
+        let mut tokens = line.split_whitespace();
+        let keyword = match tokens.next() {
+            Some(keyword) => keyword,
+            None => continue
+        };
+        let arguments: Vec<&str> = tokens.collect();
+
+        if keyword == "newmtl" {
+            if arguments.is_empty() {
+                return Err(ImportError::new_parse(path, line_number, "'newmtl' needs a material name"));
+            }
+
+            if let Some(material) = current.take() {
+                materials.insert(material.name.clone(), material);
+            }
+            current = Some(MtlMaterial::new(&arguments.join(" ")));
+            continue;
+        }
+
+        let material = match current.as_mut() {
+            Some(material) => material,
+            None => {
+                let message = format!("'{}' appears before any 'newmtl'", keyword);
+                return Err(ImportError::new_parse(path, line_number, &message));
+            }
+        };
+
+        match keyword {
+            "Kd" | "Ks" | "Ke" => {
+                let numbers = parse_numbers(path, line_number, keyword, &arguments, 1, 3)?;
+                let color = if numbers.len() == 3 {
+                    Color::new(numbers[0], numbers[1], numbers[2])
+                } else if numbers.len() == 1 {
+                    Color::new(numbers[0], numbers[0], numbers[0])
+                } else {
+                    let message = format!("'{}' expects 1 or 3 numbers, found 2", keyword);
+                    return Err(ImportError::new_parse(path, line_number, &message));
+                };
+
+                match keyword {
+                    "Kd" => material.diffuse = color,
+                    "Ks" => material.specular = color,
+                    _ => material.emission = color
+                }
+            }
+            "Ns" => {
+                material.specular_exponent = parse_numbers(path, line_number, keyword, &arguments, 1, 1)?[0];
+            }
+            "Ni" => {
+                material.refraction_index = parse_numbers(path, line_number, keyword, &arguments, 1, 1)?[0];
+            }
+            "d" => {
+                let dissolve = arguments.iter().filter(|argument| **argument != "-halo").cloned().collect::<Vec<&str>>();
+                material.dissolve = parse_numbers(path, line_number, keyword, &dissolve, 1, 1)?[0];
+            }
+            "Tr" => {
+                material.dissolve = 1.0 - parse_numbers(path, line_number, keyword, &arguments, 1, 1)?[0];
+            }
+            "Pm" => {
+                material.metallic = Some(parse_numbers(path, line_number, keyword, &arguments, 1, 1)?[0]);
+            }
+            "Pr" => {
+                material.roughness = Some(parse_numbers(path, line_number, keyword, &arguments, 1, 1)?[0]);
+            }
+            "illum" => {
+                match arguments.first().and_then(|argument| argument.parse::<u32>().ok()) {
+                    Some(illumination) if arguments.len() == 1 => material.illumination = illumination,
+                    _ => {
+                        return Err(ImportError::new_parse(path, line_number, "'illum' expects a single integer"));
+                    }
+                }
+            }
+            "map_Kd" => {
+                // options such as -s or -o come first, the file name is always last
+                match arguments.last() {
+                    Some(file_name) => {
+                        let directory = path.parent().unwrap_or_else(|| Path::new(""));
+                        material.diffuse_texture = Some(ImageTexture::load(&directory.join(file_name), true)?);
+                        material.diffuse_map = Some(file_name.to_string());
+                    }
+                    None => {
+                        return Err(ImportError::new_parse(path, line_number, "'map_Kd' needs a file name"));
+                    }
+                }
+            }
+            _ => {
+                // Ka, Tf, bump maps and other statements the renderer has no use for
+            }
+        }
+    }
+
+    if let Some(material) = current.take() {
+        materials.insert(material.name.clone(), material);
+    }
+
+    Ok(materials)
+}
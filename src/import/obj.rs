@@ -0,0 +1,412 @@
+
+use std::collections::HashMap;
+use std::path::Path;
+
+use crate::import::{ImportError, read_source, parse_numbers};
+use crate::import::mtl::{MtlMaterial, load_mtl};
+use crate::material::Material;
+use crate::math::vec3::{Vec3, Point3};
+use crate::object::triangle_mesh::{TriangleMesh, MeshFace};
+
+
+// One group/material combination of an OBJ file, with its own compacted attribute arrays.
+pub struct ObjMesh {
+    pub name: String,
+    pub material_name: String,
+    pub positions: Vec<Point3>,
+    pub normals: Vec<Vec3>,
+    pub uvs: Vec<(f64, f64)>,
+    pub faces: Vec<MeshFace>,
+    pub material: Box<dyn Material>
+}
+
+impl ObjMesh {
+    pub fn into_triangle_mesh(self) -> TriangleMesh {
+        TriangleMesh::new(self.positions, self.normals, self.uvs, self.faces, self.material)
+    }
+}
+
+#[derive(Clone, Copy)]
+struct ObjCorner {
+    position: usize,
+    uv: Option<usize>,
+    normal: Option<usize>
+}
+
+struct ObjTriangle {
+    corners: [ObjCorner; 3],
+    smoothing_group: u32,
+    group: usize,
+    material: usize
+}
+
+struct ObjParser<'a> {
+    path: &'a Path,
+    positions: Vec<Point3>,
+    normals: Vec<Vec3>,
+    uvs: Vec<(f64, f64)>,
+    triangles: Vec<ObjTriangle>,
+    groups: Vec<String>,
+    materials: Vec<MtlMaterial>,
+    material_library: HashMap<String, MtlMaterial>,
+    current_group: usize,
+    current_material: usize,
+    current_smoothing_group: u32
+}
+
+pub fn load_obj(path: &Path) -> Result<Vec<ObjMesh>, ImportError> {
+    let source = read_source(path)?;
+    parse_obj(path, &source)
+}
+
+// path is used for error messages and to resolve mtllib statements.
+pub fn parse_obj(path: &Path, source: &str) -> Result<Vec<ObjMesh>, ImportError> {
+    let mut parser = ObjParser {
+        path,
+        positions: Vec::new(),
+        normals: Vec::new(),
+        uvs: Vec::new(),
+        triangles: Vec::new(),
+        groups: vec!["default".to_string()],
+        materials: vec![MtlMaterial::new_default()],
+        material_library: HashMap::new(),
+        current_group: 0,
+        current_material: 0,
+        current_smoothing_group: 0
+    };
+
+    // joins lines continued with a trailing backslash
+    let mut pending_line = String::new();
+    let mut pending_line_number = 0;
+    for (line_index, raw_line) in source.lines().enumerate() {
+        if pending_line.is_empty() {
+            pending_line_number = line_index + 1;
+        }
+
+        let line = match raw_line.find('#') {
+            Some(comment_start) => &raw_line[.. comment_start],
+            None => raw_line
+        };
+
+        match line.trim_end().strip_suffix('\\') {
+            Some(continued) => {
+                pending_line.push_str(continued);
+                pending_line.push(' ');
+            }
+            None => {
+                pending_line.push_str(line);
+                parser.parse_line(pending_line_number, &pending_line)?;
+                pending_line.clear();
+            }
+        }
+    }
+
+    if !pending_line.is_empty() {
+        parser.parse_line(pending_line_number, &pending_line)?;
+    }
+
+    Ok(parser.build_meshes())
+}
+
+impl<'a> ObjParser<'a> {
+    fn parse_line(&mut self, line: usize, text: &str) -> Result<(), ImportError> {
+        let mut tokens = text.split_whitespace();
+        let keyword = match tokens.next() {
+            Some(keyword) => keyword,
+            None => return Ok(())
+        };
+        let arguments: Vec<&str> = tokens.collect();
+
+        match keyword {
+            "v" => {
+                // an optional w (or vertex color) may follow x y z
+                let numbers = parse_numbers(self.path, line, keyword, &arguments, 3, 7)?;
+                self.positions.push(Point3::new(numbers[0], numbers[1], numbers[2]));
+            }
+            "vn" => {
+                let numbers = parse_numbers(self.path, line, keyword, &arguments, 3, 3)?;
+                self.normals.push(Vec3::new(numbers[0], numbers[1], numbers[2]));
+            }
+            "vt" => {
+                let numbers = parse_numbers(self.path, line, keyword, &arguments, 1, 3)?;
+                let v = if numbers.len() > 1 { numbers[1] } else { 0.0 };
+                self.uvs.push((numbers[0], v));
+            }
+            "f" => {
+                self.parse_face(line, &arguments)?;
+            }
+            "g" | "o" => {
+                let name = if arguments.is_empty() { "default".to_string() } else { arguments.join(" ") };
+                self.current_group = match self.groups.iter().position(|group| *group == name) {
+                    Some(index) => index,
+                    None => {
+                        self.groups.push(name);
+                        self.groups.len() - 1
+                    }
+                };
+            }
+            "s" => {
+                self.current_smoothing_group = match arguments.first() {
+                    Some(&"off") => 0,
+                    Some(&"on") => 1,
+                    Some(value) => match value.parse::<u32>() {
+                        Ok(group) if arguments.len() == 1 => group,
+                        _ => {
+                            let message = format!("invalid smoothing group '{}'", arguments.join(" "));
+                            return Err(ImportError::new_parse(self.path, line, &message));
+                        }
+                    },
+                    None => {
+                        return Err(ImportError::new_parse(self.path, line, "'s' needs a smoothing group"));
+                    }
+                };
+            }
+            "mtllib" => {
+                if arguments.is_empty() {
+                    return Err(ImportError::new_parse(self.path, line, "'mtllib' needs a file name"));
+                }
+
+                let directory = self.path.parent().unwrap_or_else(|| Path::new(""));
+                for file_name in arguments {
+                    let library = load_mtl(&directory.join(file_name))?;
+                    self.material_library.extend(library);
+                }
+            }
+            "usemtl" => {
+                if arguments.is_empty() {
+                    return Err(ImportError::new_parse(self.path, line, "'usemtl' needs a material name"));
+                }
+
+                let name = arguments.join(" ");
+                self.current_material = match self.materials.iter().position(|material| material.name == name) {
+                    Some(index) => index,
+                    None => match self.material_library.get(&name) {
+                        Some(material) => {
+                            self.materials.push(material.clone());
+                            self.materials.len() - 1
+                        }
+                        None => {
+                            let message = format!("material '{}' is not defined in any mtllib", name);
+                            return Err(ImportError::new_parse(self.path, line, &message));
+                        }
+                    }
+                };
+            }
+            "l" | "p" | "vp" | "cstype" | "deg" | "curv" | "curv2" | "surf" | "parm" | "end" | "bmat" | "step"
+                | "trim" | "hole" | "scrv" | "sp" | "con" | "mg" | "lod" | "bevel" | "c_interp" | "d_interp"
+                | "usemap" | "maplib" | "shadow_obj" | "trace_obj" => {
+                // free-form geometry, lines, points and render attributes are not supported and skipped
+            }
+            _ => {
+                let message = format!("unknown statement '{}'", keyword);
+                return Err(ImportError::new_parse(self.path, line, &message));
+            }
+        }
+
+        Ok(())
+    }
+
+    fn parse_face(&mut self, line: usize, arguments: &[&str]) -> Result<(), ImportError> {
+        if arguments.len() < 3 {
+            let message = format!("face needs at least 3 vertices, found {}", arguments.len());
+            return Err(ImportError::new_parse(self.path, line, &message));
+        }
+
+        let mut corners: Vec<ObjCorner> = Vec::with_capacity(arguments.len());
+        for argument in arguments {
+            let mut parts = argument.split('/');
+            let position = match parts.next() {
+                Some(part) => self.resolve_index(line, part, self.positions.len(), "vertex")?,
+                None => None
+            };
+            let uv = match parts.next() {
+                Some(part) => self.resolve_index(line, part, self.uvs.len(), "texture coordinate")?,
+                None => None
+            };
+            let normal = match parts.next() {
+                Some(part) => self.resolve_index(line, part, self.normals.len(), "normal")?,
+                None => None
+            };
+
+            if parts.next().is_some() {
+                let message = format!("malformed face vertex '{}'", argument);
+                return Err(ImportError::new_parse(self.path, line, &message));
+            }
+
+            match position {
+                Some(position) => corners.push(ObjCorner { position, uv, normal }),
+                None => {
+                    let message = format!("face vertex '{}' has no position index", argument);
+                    return Err(ImportError::new_parse(self.path, line, &message));
+                }
+            }
+        }
+
+        // triangulate as a fan, which is exact for the convex polygons exporters write
+        for i in 1 .. corners.len() - 1 {
+            self.triangles.push(ObjTriangle {
+                corners: [corners[0], corners[i], corners[i + 1]],
+                smoothing_group: self.current_smoothing_group,
+                group: self.current_group,
+                material: self.current_material
+            });
+        }
+
+        Ok(())
+    }
+
+    // OBJ indices start at 1, negative indices count back from the last element read so far.
+    fn resolve_index(&self, line: usize, text: &str, count: usize, kind: &str) -> Result<Option<usize>, ImportError> {
+        if text.is_empty() {
+            return Ok(None);
+        }
+
+        let index = match text.parse::<i64>() {
+            Ok(index) => index,
+            Err(_) => {
+                let message = format!("invalid {} index '{}'", kind, text);
+                return Err(ImportError::new_parse(self.path, line, &message));
+            }
+        };
+
+        let resolved = if index > 0 {
+            index - 1
+        } else {
+            count as i64 + index
+        };
+
+        if index == 0 || resolved < 0 || resolved >= count as i64 {
+            let message = format!("{} index {} is out of range, {} defined so far", kind, index, count);
+            return Err(ImportError::new_parse(self.path, line, &message));
+        }
+
+        Ok(Some(resolved as usize))
+    }
+
+    // Area weighted vertex normals for every (position, smoothing group) pair.
+    fn compute_smooth_normals(&self) -> HashMap<(usize, u32), Vec3> {
+        let mut smooth_normals: HashMap<(usize, u32), Vec3> = HashMap::new();
+        for triangle in self.triangles.iter() {
+            if triangle.smoothing_group == 0 || triangle.corners.iter().all(|corner| corner.normal.is_some()) {
+                continue;
+            }
+
+            let p0 = self.positions[triangle.corners[0].position];
+            let p1 = self.positions[triangle.corners[1].position];
+            let p2 = self.positions[triangle.corners[2].position];
+            let face_normal = Vec3::cross(&(p1 - p0), &(p2 - p0));
+            for corner in triangle.corners.iter() {
+                let normal = smooth_normals
+                    .entry((corner.position, triangle.smoothing_group))
+                    .or_insert_with(Vec3::new_default);
+                *normal += face_normal;
+            }
+        }
+
+        smooth_normals
+    }
+
+    fn build_meshes(self) -> Vec<ObjMesh> {
+        let smooth_normals = self.compute_smooth_normals();
+
+        let mut mesh_order: Vec<(usize, usize)> = Vec::new();
+        let mut mesh_triangles: HashMap<(usize, usize), Vec<usize>> = HashMap::new();
+        for (index, triangle) in self.triangles.iter().enumerate() {
+            let key = (triangle.group, triangle.material);
+            mesh_triangles.entry(key).or_insert_with(|| {
+                mesh_order.push(key);
+                Vec::new()
+            }).push(index);
+        }
+
+        let mut meshes = Vec::with_capacity(mesh_order.len());
+        for key in mesh_order {
+            let mut positions: Vec<Point3> = Vec::new();
+            let mut normals: Vec<Vec3> = Vec::new();
+            let mut uvs: Vec<(f64, f64)> = Vec::new();
+            let mut faces: Vec<MeshFace> = Vec::new();
+
+            let mut position_map: HashMap<usize, usize> = HashMap::new();
+            let mut normal_map: HashMap<usize, usize> = HashMap::new();
+            let mut smooth_normal_map: HashMap<(usize, u32), usize> = HashMap::new();
+            let mut uv_map: HashMap<usize, usize> = HashMap::new();
+
+            for triangle_index in mesh_triangles[&key].iter() {
+                let triangle = &self.triangles[*triangle_index];
+                let mut face = MeshFace::new([0; 3]);
+
+                for (i, corner) in triangle.corners.iter().enumerate() {
+                    face.vertices[i] = *position_map.entry(corner.position).or_insert_with(|| {
+                        positions.push(self.positions[corner.position]);
+                        positions.len() - 1
+                    });
+                }
+
+                if triangle.corners.iter().all(|corner| corner.uv.is_some()) {
+                    let mut indices = [0; 3];
+                    for (i, corner) in triangle.corners.iter().enumerate() {
+                        let uv_index = corner.uv.unwrap();
+                        indices[i] = *uv_map.entry(uv_index).or_insert_with(|| {
+                            uvs.push(self.uvs[uv_index]);
+                            uvs.len() - 1
+                        });
+                    }
+                    face.uvs = Some(indices);
+                }
+
+                if triangle.corners.iter().all(|corner| corner.normal.is_some()) {
+                    let mut indices = [0; 3];
+                    for (i, corner) in triangle.corners.iter().enumerate() {
+                        let normal_index = corner.normal.unwrap();
+                        indices[i] = *normal_map.entry(normal_index).or_insert_with(|| {
+                            normals.push(self.normals[normal_index]);
+                            normals.len() - 1
+                        });
+                    }
+                    face.normals = Some(indices);
+                } else if triangle.smoothing_group != 0 {
+                    let mut indices = [0; 3];
+                    let mut is_valid = true;
+                    for (i, corner) in triangle.corners.iter().enumerate() {
+                        let smooth_key = (corner.position, triangle.smoothing_group);
+                        let smooth_normal = smooth_normals[&smooth_key];
+                        if smooth_normal.is_near_zero() {
+                            is_valid = false;
+                        }
+
+                        indices[i] = *smooth_normal_map.entry(smooth_key).or_insert_with(|| {
+                            normals.push(smooth_normal);
+                            normals.len() - 1
+                        });
+                    }
+
+                    if is_valid {
+                        face.normals = Some(indices);
+                    }
+                }
+
+                faces.push(face);
+            }
+
+            // zero normals from degenerate smoothing groups are never referenced, avoid normalizing them
+            for normal in normals.iter_mut() {
+                if normal.is_near_zero() {
+                    *normal = Vec3::new(0.0, 1.0, 0.0);
+                }
+            }
+
+            let material = &self.materials[key.1];
+            meshes.push(ObjMesh {
+                name: self.groups[key.0].clone(),
+                material_name: material.name.clone(),
+                positions,
+                normals,
+                uvs,
+                faces,
+                material: material.to_material()
+            });
+        }
+
+        meshes
+    }
+}
@@ -0,0 +1,104 @@
+
+// OBJ and MTL parsing from strings: index resolution, fan triangulation, mesh splitting and errors that
+// point at the right line.
+
+use std::path::Path;
+
+use crate::import::ImportError;
+use crate::import::obj::{ObjMesh, parse_obj};
+use crate::import::mtl::parse_mtl;
+
+
+const CUBE_CORNERS: &str = "v 0 0 0\nv 1 0 0\nv 1 1 0\nv 0 1 0\n";
+
+
+fn parse(source: &str) -> Result<Vec<ObjMesh>, ImportError> {
+    parse_obj(Path::new("test.obj"), source)
+}
+
+// line and message of a parse error, fails on success or io errors
+fn get_parse_error<T>(result: Result<T, ImportError>) -> (usize, String) {
+    match result {
+        Err(ImportError::Parse { line, message, .. }) => (line, message),
+        Err(error) => panic!("expected a parse error, got {}", error),
+        Ok(_) => panic!("expected a parse error, the source was accepted")
+    }
+}
+
+#[test]
+fn out_of_range_indices_are_rejected() {
+    let cases = [
+        ("f 1 2 5", "vertex index 5"),
+        ("f 0 1 2", "vertex index 0"),
+        ("f -5 1 2", "vertex index -5"),
+        ("f 1/1 2/1 3/1", "texture coordinate index 1"),
+        ("f 1//2 2//2 3//2", "normal index 2"),
+        ("f 1 2 x", "invalid vertex index 'x'")
+    ];
+
+    for (face, expected) in cases {
+        let source = format!("{}vn 0 0 1\n{}\n", CUBE_CORNERS, face);
+        let (line, message) = get_parse_error(parse(&source));
+        assert_eq!(line, 6, "wrong line for '{}'", face);
+        assert!(message.contains(expected), "'{}' gave '{}'", face, message);
+    }
+}
+
+#[test]
+fn indices_may_count_back_from_the_end() {
+    let meshes = parse(&format!("{}f -4 -3 -2\n", CUBE_CORNERS)).ok().unwrap();
+    assert_eq!(meshes.len(), 1);
+    assert_eq!(meshes[0].positions.len(), 3);
+    assert_eq!(meshes[0].positions[2].y, 1.0);
+}
+
+#[test]
+fn polygons_are_split_into_a_fan() {
+    let meshes = parse(&format!("{}f 1 2 3 4\n", CUBE_CORNERS)).ok().unwrap();
+    let faces = &meshes[0].faces;
+    assert_eq!(faces.len(), 2);
+    assert_eq!(faces[0].vertices, [0, 1, 2]);
+    assert_eq!(faces[1].vertices, [0, 2, 3]);
+}
+
+#[test]
+fn groups_become_separate_meshes() {
+    let source = format!("{}g first\nf 1 2 3\ng second\nf 1 3 4\ng first\nf 2 3 4\n", CUBE_CORNERS);
+    let meshes = parse(&source).ok().unwrap();
+    let names: Vec<&str> = meshes.iter().map(|mesh| mesh.name.as_str()).collect();
+    assert_eq!(names, ["first", "second"]);
+    assert_eq!(meshes[0].faces.len(), 2);
+}
+
+#[test]
+fn errors_in_continued_lines_point_at_their_first_line() {
+    let source = format!("{}f 1 2 \\\n  9\n", CUBE_CORNERS);
+    let (line, _) = get_parse_error(parse(&source));
+    assert_eq!(line, 5);
+}
+
+#[test]
+fn mtl_errors_point_at_their_line() {
+    let path = Path::new("test.mtl");
+    let (line, message) = get_parse_error(parse_mtl(path, "Kd 1 1 1\n"));
+    assert_eq!(line, 1);
+    assert!(message.contains("before any 'newmtl'"), "{}", message);
+
+    let (line, message) = get_parse_error(parse_mtl(path, "newmtl red\n\nKd 1 0\n"));
+    assert_eq!(line, 3);
+    assert!(message.contains("1 or 3 numbers"), "{}", message);
+
+    let (line, _) = get_parse_error(parse_mtl(path, "newmtl red\nNs shiny\n"));
+    assert_eq!(line, 2);
+}
+
+#[test]
+fn mtl_reads_every_material() {
+    let materials = parse_mtl(Path::new("test.mtl"), "newmtl red\nKd 0.8 0.1 0.1\nnewmtl gray\nKd 0.5\nillum 3\nKe 2\n").ok().unwrap();
+    assert_eq!(materials.len(), 2);
+    assert_eq!(materials["red"].diffuse.x, 0.8);
+    assert_eq!(materials["gray"].diffuse.z, 0.5);
+    assert_eq!(materials["gray"].illumination, 3);
+    assert_eq!(materials["gray"].emission.y, 2.0);
+    assert_eq!(materials["red"].emission.y, 0.0);
+}
@@ -6,28 +6,64 @@ mod raytracer;
 mod ray;
 mod object;
 mod world;
+mod film;
+mod display;
+mod denoise;
 mod camera;
 mod material;
+mod texture;
 mod threading;
+mod import;
+mod scene;
+mod cli;
+mod output;
+mod sampler;
+mod headless;
 
 
+use std::env;
+use std::process;
 use std::sync::mpsc::channel;
 use std::sync::mpsc::{Receiver, Sender};
 use std::thread;
 
 use speedy2d::dimen::Vector2;
-use speedy2d::image::{ImageSmoothingMode, ImageDataType};
+use speedy2d::image::{ImageSmoothingMode, ImageDataType, ImageHandle};
 use speedy2d::{Graphics2D, Window};
-use speedy2d::window::{WindowHandler, WindowHelper, VirtualKeyCode, WindowCreationOptions, WindowSize, WindowPosition};
+use speedy2d::window::{WindowHandler, WindowHelper, VirtualKeyCode, WindowCreationOptions, WindowSize, WindowPosition, UserEventSender};
 
 use raytracer::{RayTracer, RayTracerBuffer};
 use raytracer::RayTracerState;
+use scene::load_scene;
+use cli::{CliOptions, USAGE};
+use headless::run_headless;
 
 
 fn main() {
     let title = "raytracer-rs";
-    let width: usize = 1280;
-    let height: usize = 720;
+
+    let options = match CliOptions::parse(env::args().skip(1)) {
+        Ok(options) => options,
+        Err(message) => {
+            eprintln!("{}\n\n{}", message, USAGE);
+            process::exit(2);
+        }
+    };
+
+    if options.show_help {
+        println!("{}", USAGE);
+        return;
+    }
+
+    let (width, height) = options.size;
+    if options.is_headless {
+        let raytracer = create_raytracer(&options, 0, None);
+        if let Err(message) = run_headless(raytracer, &options) {
+            eprintln!("error: {}", message);
+            process::exit(1);
+        }
+        return;
+    }
 
     let mut creation_options = WindowCreationOptions::new_windowed(
         WindowSize::PhysicalPixels(Vector2::new(width as u32, height as u32)),
@@ -41,8 +77,8 @@ fn main() {
     let (command_sender, command_receiver): (Sender<RayTracerCommand>, Receiver<RayTracerCommand>) = channel();
     let (buffer_sender, buffer_receiver): (Sender<RayTracerBuffer>, Receiver<RayTracerBuffer>) = channel();
 
+    let raytracer = create_raytracer(&options, 2, Some(event_sender));
     let thread_handle = thread::spawn(move || {
-        let raytracer = RayTracer::new((width, height), 2, event_sender);
         raytracer_main(raytracer, command_receiver, buffer_sender);
     });
 
@@ -50,32 +86,143 @@ fn main() {
     window.run_loop(RTWindowHandler::new(title, command_sender, buffer_receiver));
 }
 
+// Exits the process when the scene can not be loaded, nothing has been rendered at that point.
+fn create_raytracer(options: &CliOptions, core_thread_nums: usize, event_sender: Option<UserEventSender<UserEvent>>) -> RayTracer {
+    let mut raytracer = RayTracer::new(options.size, core_thread_nums, event_sender);
+
+    // an optional scene file replaces the built-in random sphere scene
+    if let Some(scene_path) = &options.scene_path {
+        match load_scene(scene_path) {
+            Ok(scene) => raytracer.load_scene(scene),
+            Err(error) => {
+                eprintln!("failed to load scene: {}", error);
+                process::exit(1);
+            }
+        }
+    }
+
+    let mut settings = raytracer.get_settings();
+    if let Some(sample_count) = options.sample_count {
+        settings.sample_count = sample_count;
+    }
+    if let Some(adaptive_threshold) = options.adaptive_threshold {
+        settings.adaptive_threshold = adaptive_threshold;
+    }
+    if let Some(min_sample_count) = options.min_sample_count {
+        settings.min_sample_count = min_sample_count.max(2);
+    }
+    if let Some(sampler) = options.sampler {
+        settings.sampler = sampler;
+    }
+    if let Some(seed) = options.seed {
+        settings.seed = seed;
+    }
+    if let Some(tile_size) = options.tile_size {
+        settings.tile_size = tile_size;
+    }
+    if let Some(tile_order) = options.tile_order {
+        settings.tile_order = tile_order;
+    }
+    if let Some(exposure) = options.exposure {
+        settings.display.exposure = exposure;
+    }
+    if options.no_dither {
+        settings.display.dither = false;
+    }
+    if let Some(tone_mapper) = options.tone_mapper {
+        settings.display.tone_mapper = tone_mapper;
+    }
+    if let Some(white_point) = options.white_point {
+        settings.display.white_point = white_point;
+    }
+    if let Some(denoise) = options.denoise {
+        settings.denoise = denoise;
+    }
+    raytracer.set_settings(settings);
+    raytracer.set_worker_nums(options.thread_count);
+
+    raytracer
+}
+
 
 enum RayTracerCommand {
     Run,
+    Restart,
+    TogglePause,
+    Cancel,
+    CycleToneMapper,
+    CycleAov,
+    ToggleDenoised,
+    // in stops
+    ChangeExposure(f64),
     Exit
 }
 
 fn raytracer_main(mut raytracer: RayTracer, command_receiver: Receiver<RayTracerCommand>, buffer_sender: Sender<RayTracerBuffer>) {
     loop {
-        match command_receiver.try_recv() {
-            Ok(command) => {
-                match command {
-                    RayTracerCommand::Run => {
-                        if let RayTracerState::Idle = raytracer.get_raytracer_state() {
-                            raytracer.run();
-                        }
-                    }
-                    RayTracerCommand::Exit => {
-                        break;
+        // nothing to do until the next command unless a render is running
+        let command = match raytracer.get_raytracer_state() {
+            RayTracerState::Working => command_receiver.try_recv().ok(),
+            _ => match command_receiver.recv() {
+                Ok(command) => Some(command),
+                Err(_) => break
+            }
+        };
+
+        if let Some(command) = command {
+            match command {
+                RayTracerCommand::Run => {
+                    raytracer.run();
+                }
+                RayTracerCommand::Restart => {
+                    raytracer.cancel();
+                    raytracer.run();
+                }
+                RayTracerCommand::TogglePause => {
+                    match raytracer.get_raytracer_state() {
+                        RayTracerState::Working => raytracer.pause(),
+                        RayTracerState::Paused => raytracer.resume(),
+                        _ => { }
                     }
                 }
+                RayTracerCommand::Cancel => {
+                    raytracer.cancel();
+                }
+                RayTracerCommand::CycleToneMapper => {
+                    let mut display = raytracer.get_settings().display;
+                    display.tone_mapper = display.tone_mapper.get_next();
+                    raytracer.set_display_transform(display);
+                    raytracer.print_message(&format!("tone mapper {name}", name = display.tone_mapper.get_name()), true);
+                }
+                RayTracerCommand::CycleAov => {
+                    let aov = raytracer.get_aov().get_next();
+                    raytracer.set_aov(aov);
+                    raytracer.print_message(&format!("pass {name}", name = aov.get_name()), true);
+                }
+                RayTracerCommand::ToggleDenoised => {
+                    let show_denoised = !raytracer.get_show_denoised();
+                    raytracer.set_show_denoised(show_denoised);
+                    raytracer.print_message(if show_denoised { "denoised" } else { "raw" }, true);
+                }
+                RayTracerCommand::ChangeExposure(stops) => {
+                    let mut display = raytracer.get_settings().display;
+                    display.exposure += stops;
+                    raytracer.set_display_transform(display);
+                    raytracer.print_message(&format!("exposure {exposure:+.1}", exposure = display.exposure), true);
+                }
+                RayTracerCommand::Exit => {
+                    raytracer.cancel();
+                    break;
+                }
             }
-            _ => { }
         }
 
+        // tick waits for the next display update, so the window gets at most display_rate copies a second
         if let RayTracerState::Working = raytracer.get_raytracer_state() {
             raytracer.tick();
+        }
+
+        if raytracer.is_buffer_updated() {
             let copied_buffer = raytracer.consume_buffer().clone();
             buffer_sender.send(copied_buffer).unwrap();
         }
@@ -90,7 +237,9 @@ pub enum UserEvent {
 struct RTWindowHandler {
     title: String,
     command_sender: Sender<RayTracerCommand>,
-    buffer_receiver: Receiver<RayTracerBuffer>
+    buffer_receiver: Receiver<RayTracerBuffer>,
+    // drawn again on every frame until a newer buffer arrives
+    image: Option<ImageHandle>
 }
 
 impl RTWindowHandler {
@@ -98,7 +247,8 @@ impl RTWindowHandler {
         RTWindowHandler { 
             title: title.to_string(),
             command_sender,
-            buffer_receiver
+            buffer_receiver,
+            image: None
          }
     }
 
@@ -114,25 +264,27 @@ impl WindowHandler<UserEvent> for RTWindowHandler {
     }
 
     fn on_draw(&mut self, helper: &mut WindowHelper<UserEvent>, graphics: &mut Graphics2D) {
-        match self.buffer_receiver.try_recv() {
-            Ok(buffer) => {
-                let buffer_size = buffer.get_buffer_size();
-                let image_result = graphics.create_image_from_raw_pixels(
-                    ImageDataType::RGB, 
-                    ImageSmoothingMode::Linear,
-                    Vector2::new(buffer_size.0 as u32, buffer_size.1 as u32),
-                    &buffer.get_buffer());
-
-                match image_result {
-                    Ok(image) => {
-                        graphics.draw_image(Vector2::new(0.0, 0.0), &image);
-                    }
-                    Err(error) => {
-                        print!("{}", error.error().to_string());
-                    }
+        // only the newest buffer is worth drawing
+        if let Some(buffer) = self.buffer_receiver.try_iter().last() {
+            let buffer_size = buffer.get_buffer_size();
+            let image_result = graphics.create_image_from_raw_pixels(
+                ImageDataType::RGB, 
+                ImageSmoothingMode::Linear,
+                Vector2::new(buffer_size.0 as u32, buffer_size.1 as u32),
+                buffer.get_buffer());
+
+            match image_result {
+                Ok(image) => {
+                    self.image = Some(image);
+                }
+                Err(error) => {
+                    print!("{}", error.error());
                 }
             }
-            _ => { }
+        }
+
+        if let Some(image) = &self.image {
+            graphics.draw_image(Vector2::new(0.0, 0.0), image);
         }
 
         helper.request_redraw();
@@ -142,7 +294,28 @@ impl WindowHandler<UserEvent> for RTWindowHandler {
         if let Some(virtual_code) = virtual_key_code {
             match virtual_code {
                 VirtualKeyCode::R => {
-                    self.redraw_for_rt(helper);
+                    self.command_sender.send(RayTracerCommand::Restart).unwrap();
+                }
+                VirtualKeyCode::P | VirtualKeyCode::Space => {
+                    self.command_sender.send(RayTracerCommand::TogglePause).unwrap();
+                }
+                VirtualKeyCode::C | VirtualKeyCode::Escape => {
+                    self.command_sender.send(RayTracerCommand::Cancel).unwrap();
+                }
+                VirtualKeyCode::T => {
+                    self.command_sender.send(RayTracerCommand::CycleToneMapper).unwrap();
+                }
+                VirtualKeyCode::A => {
+                    self.command_sender.send(RayTracerCommand::CycleAov).unwrap();
+                }
+                VirtualKeyCode::D => {
+                    self.command_sender.send(RayTracerCommand::ToggleDenoised).unwrap();
+                }
+                VirtualKeyCode::Equals | VirtualKeyCode::Plus | VirtualKeyCode::NumpadAdd => {
+                    self.command_sender.send(RayTracerCommand::ChangeExposure(0.5)).unwrap();
+                }
+                VirtualKeyCode::Minus | VirtualKeyCode::NumpadSubtract => {
+                    self.command_sender.send(RayTracerCommand::ChangeExposure(-0.5)).unwrap();
                 }
                 _ => {}
             }
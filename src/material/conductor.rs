@@ -0,0 +1,265 @@
+
+use std::f64::consts::PI;
+
+use crate::material::{Material, ScatteredResult};
+use crate::math::vec3::{Vec3, Color};
+use crate::math::onb::Onb;
+use crate::object::HitRecord;
+use crate::ray::Ray;
+use crate::sampler::Sampler;
+use crate::texture::{Texture, sample_texture};
+use crate::texture::solid_color::SolidColor;
+
+
+// below this alpha the microfacets are too sharp to evaluate, the surface is treated as a perfect mirror
+const MIN_ALPHA: f64 = 1e-3;
+
+
+// Measured metals, the complex index of refraction is sampled at red, green and blue wavelengths.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum ConductorPreset {
+    Gold,
+    Copper,
+    Aluminum,
+    Silver
+}
+
+pub const CONDUCTOR_PRESETS: [(&str, ConductorPreset); 5] = [
+    ("gold", ConductorPreset::Gold),
+    ("copper", ConductorPreset::Copper),
+    ("aluminum", ConductorPreset::Aluminum),
+    ("aluminium", ConductorPreset::Aluminum),
+    ("silver", ConductorPreset::Silver)
+];
+
+impl ConductorPreset {
+    // (eta, k), the real and the imaginary part of the index of refraction
+    pub fn get_ior(&self) -> (Color, Color) {
+        match self {
+            ConductorPreset::Gold => (Color::new(0.143, 0.374, 1.442), Color::new(3.983, 2.385, 1.603)),
+            ConductorPreset::Copper => (Color::new(0.200, 0.924, 1.102), Color::new(3.912, 2.452, 2.142)),
+            ConductorPreset::Aluminum => (Color::new(1.657, 0.880, 0.521), Color::new(9.224, 6.269, 4.837)),
+            ConductorPreset::Silver => (Color::new(0.155, 0.117, 0.138), Color::new(4.828, 3.122, 2.147))
+        }
+    }
+}
+
+// Rough metal made of GGX microfacets (Walter et al. 2007) with the exact Fresnel term of a conductor.
+// Directions are sampled from the normals visible from the ray (Heitz 2018), so every sample reflects off
+// a facet the ray can actually see and the weights stay close to the Fresnel term even at grazing angles.
+// Roughness along the two tangents of the surface may differ, which stretches highlights like brushed metal.
+#[derive(Clone)]
+pub struct Conductor {
+    eta: Color,
+    k: Color,
+    roughness_u: Box<dyn Texture>,
+    roughness_v: Box<dyn Texture>
+}
+
+impl Conductor {
+    pub fn new_default() -> Conductor {
+        let (eta, k) = ConductorPreset::Aluminum.get_ior();
+        Conductor::new(eta, k, (0.2, 0.2))
+    }
+
+    pub fn new(eta: Color, k: Color, roughness: (f64, f64)) -> Conductor {
+        let to_texture = |roughness: f64| -> Box<dyn Texture> {
+            let roughness = roughness.clamp(0.0, 1.0);
+            Box::new(SolidColor::new(Color::new(roughness, roughness, roughness)))
+        };
+        Conductor::new_with_textures(eta, k, to_texture(roughness.0), to_texture(roughness.1))
+    }
+
+    // roughness is read from the first channel of its textures, alpha is its square
+    pub fn new_with_textures(eta: Color, k: Color, roughness_u: Box<dyn Texture>, roughness_v: Box<dyn Texture>) -> Conductor {
+        Conductor {
+            eta,
+            k,
+            roughness_u,
+            roughness_v
+        }
+    }
+
+    fn get_alpha(&self, hit_record: &HitRecord) -> (f64, f64) {
+        let to_alpha = |texture: &dyn Texture| sample_texture(texture, hit_record)[0].clamp(0.0, 1.0).powi(2);
+        (to_alpha(&*self.roughness_u), to_alpha(&*self.roughness_v))
+    }
+
+    fn is_smooth(alpha: (f64, f64)) -> bool {
+        alpha.0.max(alpha.1) < MIN_ALPHA
+    }
+
+    fn get_fresnel(&self, cos: f64) -> Color {
+        Color::new(
+            fresnel_conductor(cos, self.eta.x, self.k.x),
+            fresnel_conductor(cos, self.eta.y, self.k.y),
+            fresnel_conductor(cos, self.eta.z, self.k.z))
+    }
+
+    // x follows the tangent of the surface so roughness_u stretches highlights along u
+    fn get_frame(hit_record: &HitRecord) -> Onb {
+        Onb::new_from_w_and_tangent(&hit_record.normal, &hit_record.tangent)
+    }
+
+    // the direction towards the viewer in the tangent frame of the hit, z along the normal
+    fn get_local_outgoing(onb: &Onb, ray: &Ray) -> Vec3 {
+        onb.world_to_local(&(-ray.get_direction().get_normal()))
+    }
+}
+
+impl Material for Conductor {
+    fn scatter(&self, ray: &Ray, hit_record : &HitRecord, sampler: &mut dyn Sampler) -> Option<ScatteredResult> {
+        let onb = Conductor::get_frame(hit_record);
+        let outgoing = Conductor::get_local_outgoing(&onb, ray);
+        let alpha = self.get_alpha(hit_record);
+        // the sample is drawn even for smooth metal so the dimensions after it stay aligned
+        let u = sampler.get_2d();
+        if outgoing.z <= 0.0 {
+            return None;
+        }
+
+        if Conductor::is_smooth(alpha) {
+            let direction = Vec3::new(-outgoing.x, -outgoing.y, outgoing.z);
+            return Some(ScatteredResult {
+                attenuation: self.get_fresnel(outgoing.z),
+                scattered_ray: Ray::new(hit_record.point, onb.local_to_world(&direction)),
+                pdf: 0.0,
+                is_specular: true
+            });
+        }
+
+        let microfacet_normal = sample_visible_normal(&outgoing, alpha, u);
+        let cos_outgoing_facet = Vec3::dot(&outgoing, &microfacet_normal);
+        let direction = microfacet_normal * (2.0 * cos_outgoing_facet) - outgoing;
+        if direction.z <= 0.0 {
+            return None;
+        }
+
+        // f * cos / pdf, everything but the Fresnel term and the shadowing of the reflected ray cancels out
+        let shadowing = get_shadowing(&outgoing, &direction, alpha) / get_masking(&outgoing, alpha);
+        let scattered_direction = onb.local_to_world(&direction);
+        Some(ScatteredResult {
+            attenuation: self.get_fresnel(cos_outgoing_facet) * shadowing,
+            scattered_ray: Ray::new(hit_record.point, scattered_direction),
+            pdf: self.scattering_pdf(ray, hit_record, &scattered_direction),
+            is_specular: false
+        })
+    }
+
+    fn evaluate(&self, ray: &Ray, hit_record: &HitRecord, direction: &Vec3) -> Color {
+        let alpha = self.get_alpha(hit_record);
+        if Conductor::is_smooth(alpha) {
+            return Color::new_default();
+        }
+
+        let onb = Conductor::get_frame(hit_record);
+        let outgoing = Conductor::get_local_outgoing(&onb, ray);
+        let incoming = onb.world_to_local(&direction.get_normal());
+        if outgoing.z <= 0.0 || incoming.z <= 0.0 {
+            return Color::new_default();
+        }
+
+        let microfacet_normal = (outgoing + incoming).get_normal();
+        let distribution = get_distribution(&microfacet_normal, alpha);
+        let shadowing = get_shadowing(&outgoing, &incoming, alpha);
+        let fresnel = self.get_fresnel(Vec3::dot(&outgoing, &microfacet_normal));
+        // D G F / (4 cos_o cos_i), times cos_i
+        fresnel * (distribution * shadowing / (4.0 * outgoing.z))
+    }
+
+    fn scattering_pdf(&self, ray: &Ray, hit_record: &HitRecord, direction: &Vec3) -> f64 {
+        let alpha = self.get_alpha(hit_record);
+        if Conductor::is_smooth(alpha) {
+            return 0.0;
+        }
+
+        let onb = Conductor::get_frame(hit_record);
+        let outgoing = Conductor::get_local_outgoing(&onb, ray);
+        let incoming = onb.world_to_local(&direction.get_normal());
+        if outgoing.z <= 0.0 || incoming.z <= 0.0 {
+            return 0.0;
+        }
+
+        // density of the visible normal, turned into one of the reflected direction
+        let microfacet_normal = (outgoing + incoming).get_normal();
+        let cos_outgoing_facet = Vec3::dot(&outgoing, &microfacet_normal).max(0.0);
+        let visible_normal_pdf = get_masking(&outgoing, alpha) * cos_outgoing_facet * get_distribution(&microfacet_normal, alpha) / outgoing.z;
+        visible_normal_pdf / (4.0 * cos_outgoing_facet.max(1e-12))
+    }
+
+    // reflectance looking straight at the surface
+    fn albedo(&self, hit_record: &HitRecord) -> Color {
+        self.get_fresnel(1.0)
+    }
+}
+
+// GGX distribution of microfacet normals, normal is in the tangent frame.
+fn get_distribution(normal: &Vec3, alpha: (f64, f64)) -> f64 {
+    if normal.z <= 0.0 {
+        return 0.0;
+    }
+
+    let stretched = (normal.x / alpha.0).powi(2) + (normal.y / alpha.1).powi(2) + normal.z * normal.z;
+    1.0 / (PI * alpha.0 * alpha.1 * stretched * stretched)
+}
+
+// Smith's lambda, how much of the surface seen from direction hides behind other microfacets.
+fn get_lambda(direction: &Vec3, alpha: (f64, f64)) -> f64 {
+    let tan_squared = ((alpha.0 * direction.x).powi(2) + (alpha.1 * direction.y).powi(2)) / (direction.z * direction.z);
+    ((1.0 + tan_squared).sqrt() - 1.0) * 0.5
+}
+
+// fraction of the microfacets seen from direction that are not hidden
+fn get_masking(direction: &Vec3, alpha: (f64, f64)) -> f64 {
+    1.0 / (1.0 + get_lambda(direction, alpha))
+}
+
+// Height correlated masking and shadowing, the fraction of microfacets both directions see.
+fn get_shadowing(outgoing: &Vec3, incoming: &Vec3, alpha: (f64, f64)) -> f64 {
+    1.0 / (1.0 + get_lambda(outgoing, alpha) + get_lambda(incoming, alpha))
+}
+
+// Heitz 2018: stretch the view into the configuration of a hemisphere, pick a point on the projected
+// disk it sees and lift it back onto the hemisphere, then unstretch the normal there.
+fn sample_visible_normal(outgoing: &Vec3, alpha: (f64, f64), u: (f64, f64)) -> Vec3 {
+    let view = Vec3::new(alpha.0 * outgoing.x, alpha.1 * outgoing.y, outgoing.z).get_normal();
+
+    let length_squared = view.x * view.x + view.y * view.y;
+    let tangent = if length_squared > 0.0 {
+        Vec3::new(-view.y, view.x, 0.0) / length_squared.sqrt()
+    } else {
+        Vec3::new(1.0, 0.0, 0.0)
+    };
+    let bitangent = Vec3::cross(&view, &tangent);
+
+    let radius = u.0.sqrt();
+    let phi = 2.0 * PI * u.1;
+    let t1 = radius * phi.cos();
+    let blend = 0.5 * (1.0 + view.z);
+    let t2 = (1.0 - blend) * (1.0 - t1 * t1).sqrt() + blend * radius * phi.sin();
+
+    let normal = tangent * t1 + bitangent * t2 + view * (1.0 - t1 * t1 - t2 * t2).max(0.0).sqrt();
+    Vec3::new(alpha.0 * normal.x, alpha.1 * normal.y, normal.z.max(1e-6)).get_normal()
+}
+
+// Fresnel reflectance of a conductor with index of refraction eta + i k, averaged over both polarizations.
+fn fresnel_conductor(cos: f64, eta: f64, k: f64) -> f64 {
+    let cos = cos.clamp(0.0, 1.0);
+    let cos_squared = cos * cos;
+    let sin_squared = 1.0 - cos_squared;
+    let eta_squared = eta * eta;
+    let k_squared = k * k;
+
+    let t0 = eta_squared - k_squared - sin_squared;
+    let a_squared_plus_b_squared = (t0 * t0 + 4.0 * eta_squared * k_squared).sqrt();
+    let t1 = a_squared_plus_b_squared + cos_squared;
+    let a = (0.5 * (a_squared_plus_b_squared + t0)).max(0.0).sqrt();
+    let t2 = 2.0 * cos * a;
+    let perpendicular = (t1 - t2) / (t1 + t2);
+
+    let t3 = cos_squared * a_squared_plus_b_squared + sin_squared * sin_squared;
+    let t4 = t2 * sin_squared;
+    let parallel = perpendicular * (t3 - t4) / (t3 + t4);
+
+    0.5 * (parallel + perpendicular)
+}
@@ -1,10 +1,9 @@
 
-use rand::{thread_rng, Rng};
-
 use crate::material::{Material, ScatteredResult};
 use crate::math::vec3::{Color, Vec3};
 use crate::object::HitRecord;
 use crate::ray::Ray;
+use crate::sampler::Sampler;
 
 
 #[derive(Clone)]
@@ -30,17 +29,18 @@ impl Dielectric {
 }
 
 impl Material for Dielectric {
-    fn scatter(&self, ray: &Ray, hit_record : &HitRecord) -> Option<ScatteredResult> {
+    fn scatter(&self, ray: &Ray, hit_record : &HitRecord, sampler: &mut dyn Sampler) -> Option<ScatteredResult> {
         let refraction_ratio = if hit_record.is_front_face { 1.0 / self.refraction_index } else { self.refraction_index };
         let ray_direction = ray.get_direction().get_normal();
         let inv_ray_direction = ray_direction * -1.0;
         let cos_theta = Vec3::dot(&inv_ray_direction, &hit_record.normal).min(1.0);
         let sin_theta = (1.0 - cos_theta * cos_theta).sqrt();
 
-        let mut rng = thread_rng();
+        // drawn even under total internal reflection so the dimensions after it stay aligned
+        let reflect_u = sampler.get_1d();
         let cannot_refract = 
             refraction_ratio * sin_theta > 1.0 ||
-            Dielectric::reflectance(cos_theta, refraction_ratio) > rng.gen_range(0.0 .. 1.0);
+            Dielectric::reflectance(cos_theta, refraction_ratio) > reflect_u;
 
         let refracted = if cannot_refract {
             ray_direction.reflect(&hit_record.normal)
@@ -55,7 +55,9 @@ impl Material for Dielectric {
 
         let result = ScatteredResult { 
             attenuation: albedo, 
-            scattered_ray
+            scattered_ray,
+            pdf: 0.0,
+            is_specular: true
         };
 
         Some(result)
@@ -0,0 +1,36 @@
+
+use crate::material::{Material, ScatteredResult};
+use crate::math::vec3::Color;
+use crate::object::HitRecord;
+use crate::ray::Ray;
+use crate::sampler::Sampler;
+
+
+#[derive(Clone)]
+pub struct DiffuseLight {
+    emit: Color
+}
+
+impl DiffuseLight {
+    pub fn new_default() -> DiffuseLight {
+        DiffuseLight::new(Color::new(1.0, 1.0, 1.0))
+    }
+
+    pub fn new(emit: Color) -> DiffuseLight {
+        DiffuseLight { emit }
+    }
+}
+
+impl Material for DiffuseLight {
+    fn scatter(&self, ray: &Ray, hit_record : &HitRecord, sampler: &mut dyn Sampler) -> Option<ScatteredResult> {
+        None
+    }
+
+    fn emitted(&self, hit_record: &HitRecord) -> Color {
+        self.emit
+    }
+
+    fn is_emissive(&self) -> bool {
+        !self.emit.is_near_zero()
+    }
+}
@@ -2,6 +2,7 @@
 use crate::material::{Material, ScatteredResult};
 use crate::object::HitRecord;
 use crate::ray::Ray;
+use crate::sampler::Sampler;
 
 
 #[derive(Clone)]
@@ -16,7 +17,7 @@ impl ErrorMat {
 }
 
 impl Material for ErrorMat {
-    fn scatter(&self, ray: &Ray, hit_record : &HitRecord) -> Option<ScatteredResult> {
+    fn scatter(&self, ray: &Ray, hit_record : &HitRecord, sampler: &mut dyn Sampler) -> Option<ScatteredResult> {
         None
     }
 }
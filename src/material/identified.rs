@@ -0,0 +1,51 @@
+
+use crate::material::{Material, ScatteredResult};
+use crate::math::vec3::{Vec3, Color};
+use crate::object::HitRecord;
+use crate::ray::Ray;
+use crate::sampler::Sampler;
+
+
+// Gives a material an id for the material id pass and otherwise behaves exactly like it.
+// Materials are cloned into every object using them, so the id travels along with the clones.
+#[derive(Clone)]
+pub struct IdentifiedMaterial {
+    id: u32,
+    material: Box<dyn Material>
+}
+
+impl IdentifiedMaterial {
+    pub fn new(id: u32, material: Box<dyn Material>) -> IdentifiedMaterial {
+        IdentifiedMaterial { id, material }
+    }
+}
+
+impl Material for IdentifiedMaterial {
+    fn scatter(&self, ray: &Ray, hit_record : &HitRecord, sampler: &mut dyn Sampler) -> Option<ScatteredResult> {
+        self.material.scatter(ray, hit_record, sampler)
+    }
+
+    fn emitted(&self, hit_record: &HitRecord) -> Color {
+        self.material.emitted(hit_record)
+    }
+
+    fn is_emissive(&self) -> bool {
+        self.material.is_emissive()
+    }
+
+    fn evaluate(&self, ray: &Ray, hit_record: &HitRecord, direction: &Vec3) -> Color {
+        self.material.evaluate(ray, hit_record, direction)
+    }
+
+    fn scattering_pdf(&self, ray: &Ray, hit_record: &HitRecord, direction: &Vec3) -> f64 {
+        self.material.scattering_pdf(ray, hit_record, direction)
+    }
+
+    fn albedo(&self, hit_record: &HitRecord) -> Color {
+        self.material.albedo(hit_record)
+    }
+
+    fn get_id(&self) -> u32 {
+        self.id
+    }
+}
@@ -1,29 +1,42 @@
 
+use std::f64::consts::PI;
+
 use crate::material::{Material, ScatteredResult};
 use crate::math::vec3::Color;
 use crate::object::HitRecord;
 use crate::ray::Ray;
+use crate::sampler::Sampler;
 use crate::math::vec3::Vec3;
+use crate::texture::{Texture, sample_texture};
+use crate::texture::solid_color::SolidColor;
 
 
 #[derive(Clone)]
 pub struct Lambertian {
-    albedo: Color
+    albedo: Box<dyn Texture>
 }
 
 impl Lambertian {
     pub fn new_default() -> Lambertian {
-        Lambertian { albedo: Color::new(0.0, 0.0, 0.0) }
+        Lambertian::new(Color::new(0.0, 0.0, 0.0))
     }
 
     pub fn new(albedo: Color) -> Lambertian {
+        Lambertian::new_with_texture(Box::new(SolidColor::new(albedo)))
+    }
+
+    pub fn new_with_texture(albedo: Box<dyn Texture>) -> Lambertian {
         Lambertian { albedo }
     }
+
+    fn get_albedo(&self, hit_record: &HitRecord) -> Color {
+        sample_texture(&*self.albedo, hit_record)
+    }
 }
 
 impl Material for Lambertian {
-    fn scatter(&self, ray: &Ray, hit_record : &HitRecord) -> Option<ScatteredResult> {
-        let mut scattered_direction = hit_record.normal + Vec3::rand_in_unit_sphere().get_normal();
+    fn scatter(&self, ray: &Ray, hit_record : &HitRecord, sampler: &mut dyn Sampler) -> Option<ScatteredResult> {
+        let mut scattered_direction = hit_record.normal + Vec3::sample_unit_vector(sampler.get_2d());
         if scattered_direction.is_near_zero() {
             scattered_direction = hit_record.normal;
         }
@@ -32,11 +45,28 @@ impl Material for Lambertian {
             hit_record.point, 
             scattered_direction);
 
+        // normal plus a unit vector is cosine distributed, so albedo is already f * cos / pdf
+        let pdf = self.scattering_pdf(ray, hit_record, scattered_ray.get_direction());
         let result = ScatteredResult { 
-            attenuation: self.albedo, 
-            scattered_ray 
+            attenuation: self.get_albedo(hit_record), 
+            scattered_ray,
+            pdf,
+            is_specular: false
         };
 
         Some(result)
     }
+
+    fn evaluate(&self, ray: &Ray, hit_record: &HitRecord, direction: &Vec3) -> Color {
+        self.get_albedo(hit_record) * self.scattering_pdf(ray, hit_record, direction)
+    }
+
+    fn scattering_pdf(&self, ray: &Ray, hit_record: &HitRecord, direction: &Vec3) -> f64 {
+        let cos = Vec3::dot(&hit_record.normal, &direction.get_normal());
+        cos.max(0.0) / PI
+    }
+
+    fn albedo(&self, hit_record: &HitRecord) -> Color {
+        self.get_albedo(hit_record)
+    }
 }
@@ -3,50 +3,69 @@ use crate::material::{Material, ScatteredResult};
 use crate::math::vec3::{Vec3, Color};
 use crate::object::HitRecord;
 use crate::ray::Ray;
+use crate::sampler::Sampler;
+use crate::texture::{Texture, sample_texture};
+use crate::texture::solid_color::SolidColor;
 
 
 #[derive(Clone)]
 pub struct Metal {
-    albedo: Color,
-    fuzziness: f64
+    albedo: Box<dyn Texture>,
+    fuzziness: Box<dyn Texture>
 }
 
 impl Metal {
     pub fn new_default() -> Metal {
-        Metal { 
-            albedo: Color::new(0.0, 0.0, 0.0),
-            fuzziness: 0.0
-        }
+        Metal::new(Color::new(0.0, 0.0, 0.0), 0.0)
     }
 
     pub fn new(albedo: Color, fuzziness: f64) -> Metal {
         let clamped_fuzziness = fuzziness.clamp(0.0, 1.0);
-        Metal { 
-            albedo, 
-            fuzziness: clamped_fuzziness
+        Metal::new_with_textures(
+            Box::new(SolidColor::new(albedo)),
+            Box::new(SolidColor::new(Color::new(clamped_fuzziness, clamped_fuzziness, clamped_fuzziness))))
+    }
+
+    // fuzziness is read from the first channel of its texture
+    pub fn new_with_textures(albedo: Box<dyn Texture>, fuzziness: Box<dyn Texture>) -> Metal {
+        Metal {
+            albedo,
+            fuzziness
         }
     }
 }
 
 impl Material for Metal {
-    fn scatter(&self, ray: &Ray, hit_record : &HitRecord) -> Option<ScatteredResult> {
+    fn scatter(&self, ray: &Ray, hit_record : &HitRecord, sampler: &mut dyn Sampler) -> Option<ScatteredResult> {
         let unit_ray_direction = ray.get_direction().get_normal();
         let mut reflected_direction = unit_ray_direction.reflect(&hit_record.normal);
 
-        let fuzzy_vector = Vec3::rand_in_unit_sphere() * self.fuzziness;
+        let fuzziness = sample_texture(&*self.fuzziness, hit_record)[0].clamp(0.0, 1.0);
+        let fuzzy_vector = Vec3::sample_in_unit_sphere(sampler.get_2d(), sampler.get_1d()) * fuzziness;
         reflected_direction += fuzzy_vector;
         reflected_direction.normalize();
 
+        // fuzz can tip the reflection into the surface, that light is absorbed instead of leaking through
+        if Vec3::dot(&reflected_direction, &hit_record.normal) <= 0.0 {
+            return None;
+        }
+
         let scattered_ray = Ray::new(
             hit_record.point, 
             reflected_direction);
 
         let result = ScatteredResult { 
-            attenuation: self.albedo, 
-            scattered_ray
+            attenuation: sample_texture(&*self.albedo, hit_record), 
+            scattered_ray,
+            pdf: 0.0,
+            is_specular: true
         };
 
         Some(result)
     }
+
+    fn albedo(&self, hit_record: &HitRecord) -> Color {
+        sample_texture(&*self.albedo, hit_record)
+    }
 }
 
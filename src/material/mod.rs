@@ -3,21 +3,61 @@ pub mod errormat;
 pub mod lambertian;
 pub mod metal;
 pub mod dielectric;
+pub mod conductor;
+pub mod diffuse_light;
+pub mod identified;
+
+#[cfg(test)]
+mod tests;
 
 use dyn_clone::DynClone;
 
-use crate::math::vec3::Color;
+use crate::math::vec3::{Vec3, Color};
 use crate::object::HitRecord;
 use crate::ray::Ray;
+use crate::sampler::Sampler;
 
 
 pub struct ScatteredResult {
     pub attenuation : Color,
-    pub scattered_ray : Ray
+    pub scattered_ray : Ray,
+    // solid angle pdf of the scattered direction, meaningless for specular results
+    pub pdf : f64,
+    pub is_specular : bool
 }
 
-pub trait Material: Send + DynClone {
-    fn scatter(&self, ray: &Ray, hit_record : &HitRecord) -> Option<ScatteredResult>;
+pub trait Material: Send + Sync + DynClone {
+    fn scatter(&self, ray: &Ray, hit_record : &HitRecord, sampler: &mut dyn Sampler) -> Option<ScatteredResult>;
+
+    // radiance leaving the surface on its own, added on top of whatever is scattered
+    fn emitted(&self, hit_record: &HitRecord) -> Color {
+        Color::new_default()
+    }
+
+    fn is_emissive(&self) -> bool {
+        false
+    }
+
+    // BSDF times the cosine term towards direction, used when sampling lights directly.
+    // Specular materials can not be evaluated for an arbitrary direction and keep the default.
+    fn evaluate(&self, ray: &Ray, hit_record: &HitRecord, direction: &Vec3) -> Color {
+        Color::new_default()
+    }
+
+    // pdf with which scatter would have picked direction
+    fn scattering_pdf(&self, ray: &Ray, hit_record: &HitRecord, direction: &Vec3) -> f64 {
+        0.0
+    }
+
+    // surface color without any lighting, for the albedo pass
+    fn albedo(&self, hit_record: &HitRecord) -> Color {
+        Color::new(1.0, 1.0, 1.0)
+    }
+
+    // zero unless the material was given an id, see IdentifiedMaterial
+    fn get_id(&self) -> u32 {
+        0
+    }
 }
 
 dyn_clone::clone_trait_object!(Material);
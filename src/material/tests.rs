@@ -0,0 +1,435 @@
+
+// Statistical checks every material has to pass: white furnace (a white material never returns more
+// energy than it receives), weights that agree with evaluate and scattering_pdf, reciprocity of the BSDF
+// and a chi-square test of the sampled directions against scattering_pdf.
+// Surfaces face +z at the origin, outgoing directions point away from the surface towards the viewer.
+
+use std::f64::consts::PI;
+
+use crate::material::Material;
+use crate::material::lambertian::Lambertian;
+use crate::material::metal::Metal;
+use crate::material::dielectric::Dielectric;
+use crate::material::conductor::{Conductor, ConductorPreset};
+use crate::math::vec3::{Vec3, Point3, Color};
+use crate::object::HitRecord;
+use crate::ray::Ray;
+use crate::sampler::{Sampler, SamplerType};
+
+
+const SAMPLE_COUNT: u32 = 200_000;
+
+const THETA_BINS: usize = 10;
+const PHI_BINS: usize = 20;
+// bins expected to get fewer samples are pooled, the chi-square statistic is unreliable for them
+const MIN_EXPECTED_COUNT: f64 = 5.0;
+// Wilson-Hilferty z score above which the sampled directions are rejected, about one in 10000 by chance
+const MAX_CHI_SQUARE_Z: f64 = 3.7;
+
+// cosines between the outgoing direction and the normal, from grazing to head-on
+const OUTGOING_COSINES: [f64; 4] = [0.1, 0.4, 0.7, 1.0];
+
+
+fn get_outgoing_directions() -> Vec<Vec3> {
+    OUTGOING_COSINES.iter().map(|cos| {
+        let sin = (1.0 - cos * cos).max(0.0).sqrt();
+        Vec3::new(sin * 0.6, sin * 0.8, *cos)
+    }).collect()
+}
+
+fn get_direction(theta: f64, phi: f64) -> Vec3 {
+    Vec3::new(theta.sin() * phi.cos(), theta.sin() * phi.sin(), theta.cos())
+}
+
+fn make_record(material: &dyn Material, is_front_face: bool) -> HitRecord<'_> {
+    HitRecord {
+        point: Point3::new_default(),
+        normal: Vec3::new(0.0, 0.0, 1.0),
+        weight: 1.0,
+        uv: (0.5, 0.5),
+        tangent: Vec3::new(1.0, 0.0, 0.0),
+        uv_footprint: 0.0,
+        is_front_face,
+        object_id: 0,
+        material
+    }
+}
+
+// the ray that arrives at the surface from outgoing
+fn make_ray(outgoing: &Vec3) -> Ray {
+    Ray::new(*outgoing, -*outgoing)
+}
+
+fn make_sampler() -> Box<dyn Sampler> {
+    SamplerType::Independent.create(SAMPLE_COUNT, 1)
+}
+
+// Average weight of the rays scatter returns, absorbed samples count as zero.
+fn get_furnace_albedo(material: &dyn Material, outgoing: &Vec3, is_front_face: bool) -> Color {
+    let record = make_record(material, is_front_face);
+    let ray = make_ray(outgoing);
+    let mut sampler = make_sampler();
+
+    let mut sum = Color::new_default();
+    for sample_index in 0 .. SAMPLE_COUNT {
+        sampler.start_sample((0, 0), sample_index);
+        if let Some(result) = material.scatter(&ray, &record, &mut *sampler) {
+            sum += result.attenuation;
+        }
+    }
+    sum / SAMPLE_COUNT as f64
+}
+
+// Integral of evaluate over the sphere, what light sampling sees of the material.
+// A midpoint rule on a fine grid, random directions would rarely land in the lobe of smooth materials.
+fn get_evaluated_albedo(material: &dyn Material, outgoing: &Vec3) -> Color {
+    let record = make_record(material, true);
+    let ray = make_ray(outgoing);
+
+    let (theta_steps, phi_steps) = (512, 512);
+    let theta_step = PI / theta_steps as f64;
+    let phi_step = 2.0 * PI / phi_steps as f64;
+
+    let mut sum = Color::new_default();
+    for theta_index in 0 .. theta_steps {
+        let theta = (theta_index as f64 + 0.5) * theta_step;
+        for phi_index in 0 .. phi_steps {
+            let phi = (phi_index as f64 + 0.5) * phi_step;
+            let solid_angle = theta.sin() * theta_step * phi_step;
+            sum += material.evaluate(&ray, &record, &get_direction(theta, phi)) * solid_angle;
+        }
+    }
+    sum
+}
+
+// A white material may absorb energy but never create it, lossless ones give back exactly what came in.
+fn assert_furnace(material: &dyn Material, outgoing: &Vec3, is_front_face: bool, is_lossless: bool) {
+    let albedo = get_furnace_albedo(material, outgoing, is_front_face);
+    for channel in 0 .. 3 {
+        assert!(albedo[channel] <= 1.0 + 1e-9, "furnace gained energy: {} for outgoing cos {}", albedo[channel], outgoing.z);
+        assert!(!is_lossless || albedo[channel] >= 1.0 - 1e-9, "furnace lost energy: {} for outgoing cos {}", albedo[channel], outgoing.z);
+    }
+}
+
+// Weights of scattered rays have to be evaluate / scattering_pdf, or light sampling and bsdf sampling disagree.
+fn assert_weights_match_evaluate(material: &dyn Material) {
+    let mut sampler = make_sampler();
+    for outgoing in get_outgoing_directions() {
+        let record = make_record(material, true);
+        let ray = make_ray(&outgoing);
+        for sample_index in 0 .. 1000 {
+            sampler.start_sample((0, 0), sample_index);
+            let result = match material.scatter(&ray, &record, &mut *sampler) {
+                Some(result) => result,
+                None => continue
+            };
+
+            let direction = result.scattered_ray.get_direction().get_normal();
+            let pdf = material.scattering_pdf(&ray, &record, &direction);
+            assert!((pdf - result.pdf).abs() <= 1e-6 * pdf.max(1.0), "scatter reported pdf {} but scattering_pdf is {}", result.pdf, pdf);
+            if pdf <= 1e-6 {
+                continue;
+            }
+
+            let expected = material.evaluate(&ray, &record, &direction) / pdf;
+            for channel in 0 .. 3 {
+                assert!((result.attenuation[channel] - expected[channel]).abs() <= 1e-6 * expected[channel].max(1.0),
+                    "weight {} does not match evaluate / pdf {}", result.attenuation[channel], expected[channel]);
+            }
+        }
+    }
+}
+
+// f(wo, wi) has to equal f(wi, wo), evaluate includes the cosine towards direction so it is divided out.
+fn assert_reciprocity(material: &dyn Material) {
+    let record = make_record(material, true);
+    let mut sampler = make_sampler();
+    for sample_index in 0 .. 1000 {
+        sampler.start_sample((0, 0), sample_index);
+        let mut first = Vec3::sample_unit_vector(sampler.get_2d());
+        let mut second = Vec3::sample_unit_vector(sampler.get_2d());
+        first.z = first.z.abs().max(1e-3);
+        second.z = second.z.abs().max(1e-3);
+        let (first, second) = (first.get_normal(), second.get_normal());
+
+        let forward = material.evaluate(&make_ray(&first), &record, &second) / second.z;
+        let backward = material.evaluate(&make_ray(&second), &record, &first) / first.z;
+        for channel in 0 .. 3 {
+            assert!((forward[channel] - backward[channel]).abs() <= 1e-9 * forward[channel].abs().max(1.0),
+                "bsdf is not reciprocal: {} against {}", forward[channel], backward[channel]);
+        }
+    }
+}
+
+// Bins the directions scatter picks over the sphere and compares them with what scattering_pdf predicts.
+// Returns the Wilson-Hilferty z score of the chi-square statistic, it is roughly standard normal when they agree.
+fn get_chi_square_z(material: &dyn Material, outgoing: &Vec3) -> f64 {
+    let record = make_record(material, true);
+    let ray = make_ray(outgoing);
+    let mut sampler = make_sampler();
+
+    let theta_step = PI / THETA_BINS as f64;
+    let phi_step = 2.0 * PI / PHI_BINS as f64;
+
+    let mut observed = vec![0.0; THETA_BINS * PHI_BINS];
+    for sample_index in 0 .. SAMPLE_COUNT {
+        sampler.start_sample((0, 0), sample_index);
+        let direction = match material.scatter(&ray, &record, &mut *sampler) {
+            Some(result) => result.scattered_ray.get_direction().get_normal(),
+            None => continue
+        };
+
+        let theta = direction.z.clamp(-1.0, 1.0).acos();
+        let phi = direction.y.atan2(direction.x).rem_euclid(2.0 * PI);
+        let theta_bin = ((theta / theta_step) as usize).min(THETA_BINS - 1);
+        let phi_bin = ((phi / phi_step) as usize).min(PHI_BINS - 1);
+        observed[theta_bin * PHI_BINS + phi_bin] += 1.0;
+    }
+
+    // midpoint rule over a finer grid inside every bin, coarser grids miss the peaks of narrow lobes
+    let substeps = 32;
+    let mut expected = vec![0.0; THETA_BINS * PHI_BINS];
+    for theta_bin in 0 .. THETA_BINS {
+        for phi_bin in 0 .. PHI_BINS {
+            let mut integral = 0.0;
+            for theta_substep in 0 .. substeps {
+                for phi_substep in 0 .. substeps {
+                    let theta = (theta_bin as f64 + (theta_substep as f64 + 0.5) / substeps as f64) * theta_step;
+                    let phi = (phi_bin as f64 + (phi_substep as f64 + 0.5) / substeps as f64) * phi_step;
+                    let pdf = material.scattering_pdf(&ray, &record, &get_direction(theta, phi));
+                    integral += pdf * theta.sin() * theta_step * phi_step / (substeps * substeps) as f64;
+                }
+            }
+            expected[theta_bin * PHI_BINS + phi_bin] = integral * SAMPLE_COUNT as f64;
+        }
+    }
+
+    let mut chi_square = 0.0;
+    let mut degrees_of_freedom = 0;
+    let (mut pooled_observed, mut pooled_expected) = (0.0, 0.0);
+    for (observed, expected) in observed.iter().zip(expected.iter()) {
+        if *expected < MIN_EXPECTED_COUNT {
+            pooled_observed += observed;
+            pooled_expected += expected;
+            continue;
+        }
+
+        chi_square += (observed - expected).powi(2) / expected;
+        degrees_of_freedom += 1;
+    }
+    if pooled_expected >= MIN_EXPECTED_COUNT {
+        chi_square += (pooled_observed - pooled_expected).powi(2) / pooled_expected;
+        degrees_of_freedom += 1;
+    } else {
+        // a handful of samples where the pdf says there should be none is still a mismatch
+        assert!(pooled_observed <= MIN_EXPECTED_COUNT + 10.0 * pooled_expected.max(1.0),
+            "{} samples fell where scattering_pdf expects {}", pooled_observed, pooled_expected);
+    }
+
+    let degrees_of_freedom = (degrees_of_freedom - 1).max(1) as f64;
+    let variance = 2.0 / (9.0 * degrees_of_freedom);
+    ((chi_square / degrees_of_freedom).cbrt() - (1.0 - variance)) / variance.sqrt()
+}
+
+fn assert_chi_square(material: &dyn Material) {
+    for outgoing in get_outgoing_directions() {
+        let z = get_chi_square_z(material, &outgoing);
+        assert!(z < MAX_CHI_SQUARE_Z, "sampled directions do not follow scattering_pdf for outgoing cos {} (z score {})", outgoing.z, z);
+    }
+}
+
+
+#[test]
+fn lambertian_white_furnace() {
+    let material = Lambertian::new(Color::new(1.0, 1.0, 1.0));
+    for outgoing in get_outgoing_directions() {
+        assert_furnace(&material, &outgoing, true, true);
+
+        let albedo = get_evaluated_albedo(&material, &outgoing);
+        assert!((albedo[0] - 1.0).abs() < 1e-3, "evaluate integrates to {} instead of 1", albedo[0]);
+    }
+}
+
+#[test]
+fn lambertian_weights_match_evaluate() {
+    assert_weights_match_evaluate(&Lambertian::new(Color::new(0.8, 0.5, 0.2)));
+}
+
+#[test]
+fn lambertian_reciprocity() {
+    assert_reciprocity(&Lambertian::new(Color::new(0.8, 0.5, 0.2)));
+}
+
+#[test]
+fn lambertian_chi_square() {
+    assert_chi_square(&Lambertian::new(Color::new(0.8, 0.5, 0.2)));
+}
+
+#[test]
+fn metal_white_furnace() {
+    // a perfect mirror reflects everything, fuzz may only lose energy into the surface
+    let mirror = Metal::new(Color::new(1.0, 1.0, 1.0), 0.0);
+    for outgoing in get_outgoing_directions() {
+        assert_furnace(&mirror, &outgoing, true, true);
+    }
+
+    for fuzziness in [0.3, 1.0] {
+        let material = Metal::new(Color::new(1.0, 1.0, 1.0), fuzziness);
+        for outgoing in get_outgoing_directions() {
+            assert_furnace(&material, &outgoing, true, false);
+        }
+    }
+}
+
+#[test]
+fn metal_never_scatters_into_the_surface() {
+    let mut sampler = make_sampler();
+    let material = Metal::new(Color::new(1.0, 1.0, 1.0), 1.0);
+    let record = make_record(&material, true);
+    for outgoing in get_outgoing_directions() {
+        let ray = make_ray(&outgoing);
+        for sample_index in 0 .. 10000 {
+            sampler.start_sample((0, 0), sample_index);
+            if let Some(result) = material.scatter(&ray, &record, &mut *sampler) {
+                assert!(result.scattered_ray.get_direction().z > 0.0, "metal scattered below the surface for outgoing cos {}", outgoing.z);
+            }
+        }
+    }
+}
+
+#[test]
+fn dielectric_white_furnace() {
+    // clear glass only splits the light between reflection and refraction, from either side
+    let material = Dielectric::new(1.5);
+    for outgoing in get_outgoing_directions() {
+        assert_furnace(&material, &outgoing, true, true);
+        assert_furnace(&material, &outgoing, false, true);
+    }
+}
+
+#[test]
+fn dielectric_reflects_the_fresnel_fraction() {
+    let material = Dielectric::new(1.5);
+    let record = make_record(&material, true);
+    let mut sampler = make_sampler();
+    for outgoing in get_outgoing_directions() {
+        let ray = make_ray(&outgoing);
+        let mut reflected_count = 0;
+        for sample_index in 0 .. SAMPLE_COUNT {
+            sampler.start_sample((0, 0), sample_index);
+            let result = material.scatter(&ray, &record, &mut *sampler).unwrap();
+            if result.scattered_ray.get_direction().z > 0.0 {
+                reflected_count += 1;
+            }
+        }
+
+        // Schlick's approximation, which the material uses
+        let r0 = ((1.0 - 1.5) / (1.0 + 1.5_f64)).powi(2);
+        let expected = r0 + (1.0 - r0) * (1.0 - outgoing.z).powi(5);
+        let fraction = reflected_count as f64 / SAMPLE_COUNT as f64;
+        let standard_error = (expected * (1.0 - expected) / SAMPLE_COUNT as f64).sqrt();
+        assert!((fraction - expected).abs() <= 5.0 * standard_error + 1e-9,
+            "reflected {} of the light instead of {} for outgoing cos {}", fraction, expected, outgoing.z);
+    }
+}
+
+fn get_test_conductors() -> Vec<Conductor> {
+    let (eta, k) = ConductorPreset::Gold.get_ior();
+    vec![
+        Conductor::new(eta, k, (0.3, 0.3)),
+        Conductor::new(eta, k, (0.8, 0.8)),
+        // brushed, much rougher across than along
+        Conductor::new(eta, k, (0.15, 0.6))
+    ]
+}
+
+#[test]
+fn conductor_white_furnace() {
+    // a conductor that reflects everything at every angle, single scattering GGX may only lose energy
+    let white = Conductor::new(Color::new(0.0, 0.0, 0.0), Color::new(1e4, 1e4, 1e4), (0.5, 0.5));
+    for outgoing in get_outgoing_directions() {
+        assert_furnace(&white, &outgoing, true, false);
+    }
+
+    // scatter and light sampling have to see the same amount of light
+    for material in get_test_conductors() {
+        for outgoing in get_outgoing_directions() {
+            let sampled = get_furnace_albedo(&material, &outgoing, true);
+            let evaluated = get_evaluated_albedo(&material, &outgoing);
+            for channel in 0 .. 3 {
+                assert!((sampled[channel] - evaluated[channel]).abs() < 0.005,
+                    "scatter reflects {} but evaluate integrates to {} for outgoing cos {}", sampled[channel], evaluated[channel], outgoing.z);
+            }
+        }
+    }
+}
+
+#[test]
+fn conductor_weights_match_evaluate() {
+    for material in get_test_conductors() {
+        assert_weights_match_evaluate(&material);
+    }
+}
+
+#[test]
+fn conductor_reciprocity() {
+    for material in get_test_conductors() {
+        assert_reciprocity(&material);
+    }
+}
+
+#[test]
+fn conductor_chi_square() {
+    for material in get_test_conductors() {
+        assert_chi_square(&material);
+    }
+}
+
+#[test]
+fn smooth_conductor_is_a_mirror() {
+    let (eta, k) = ConductorPreset::Silver.get_ior();
+    let material = Conductor::new(eta, k, (0.0, 0.0));
+    let record = make_record(&material, true);
+    let mut sampler = make_sampler();
+    for outgoing in get_outgoing_directions() {
+        sampler.start_sample((0, 0), 0);
+        let result = material.scatter(&make_ray(&outgoing), &record, &mut *sampler).unwrap();
+        let direction = result.scattered_ray.get_direction().get_normal();
+        assert!(result.is_specular);
+        assert!((direction - Vec3::new(-outgoing.x, -outgoing.y, outgoing.z)).length() < 1e-9, "smooth conductor did not mirror the ray");
+        assert!(result.attenuation[0] > 0.9 && result.attenuation[0] <= 1.0, "silver reflects {} of red", result.attenuation[0]);
+    }
+}
+
+// Mean distance of the scattered directions from the mirror direction along world x and y.
+fn get_lobe_spread(material: &dyn Material, tangent: Vec3) -> (f64, f64) {
+    let mut record = make_record(material, true);
+    record.tangent = tangent;
+    let ray = make_ray(&Vec3::new(0.0, 0.0, 1.0));
+    let mut sampler = make_sampler();
+
+    let mut spread = (0.0, 0.0);
+    for sample_index in 0 .. 10_000 {
+        sampler.start_sample((0, 0), sample_index);
+        if let Some(result) = material.scatter(&ray, &record, &mut *sampler) {
+            let direction = result.scattered_ray.get_direction().get_normal();
+            spread.0 += direction.x.abs();
+            spread.1 += direction.y.abs();
+        }
+    }
+    spread
+}
+
+#[test]
+fn anisotropic_conductor_follows_the_tangent() {
+    let (eta, k) = ConductorPreset::Aluminum.get_ior();
+    let material = Conductor::new(eta, k, (0.5, 0.1));
+
+    // roughness_u is along the tangent, so the lobe is wide along it and narrow across it
+    let along_x = get_lobe_spread(&material, Vec3::new(1.0, 0.0, 0.0));
+    assert!(along_x.0 > 3.0 * along_x.1, "lobe spread {:?} with the tangent along x", along_x);
+
+    // only the part of the tangent in the surface counts
+    let along_y = get_lobe_spread(&material, Vec3::new(0.0, 1.0, 1.0));
+    assert!(along_y.1 > 3.0 * along_y.0, "lobe spread {:?} with the tangent along y", along_y);
+}
@@ -0,0 +1,18 @@
+
+// splitmix64 finalizer, spreads every input bit over the whole output
+pub fn mix_bits(value: u64) -> u64 {
+    let mut value = value.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    value = (value ^ (value >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    value = (value ^ (value >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    value ^ (value >> 31)
+}
+
+// Combines values into one hash, the order matters.
+pub fn hash_values(values: &[u64]) -> u64 {
+    values.iter().fold(0, |hash, value| mix_bits(hash ^ value))
+}
+
+// uniform in [0, 1) from the upper bits of a hash
+pub fn to_unit_float(hash: u64) -> f64 {
+    (hash >> 11) as f64 / (1u64 << 53) as f64
+}
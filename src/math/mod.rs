@@ -1,2 +1,5 @@
 
 pub mod vec3;
+pub mod onb;
+pub mod noise;
+pub mod hash;
@@ -0,0 +1,170 @@
+
+use rand::SeedableRng;
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+
+use crate::math::hash::mix_bits;
+use crate::math::vec3::{Vec3, Point3};
+
+
+const PERMUTATION_SIZE: usize = 256;
+
+// Gradients pointing to the edge midpoints of a cube, from Perlin's improved noise.
+const GRADIENTS: [(f64, f64, f64); 12] = [
+    (1.0, 1.0, 0.0), (-1.0, 1.0, 0.0), (1.0, -1.0, 0.0), (-1.0, -1.0, 0.0),
+    (1.0, 0.0, 1.0), (-1.0, 0.0, 1.0), (1.0, 0.0, -1.0), (-1.0, 0.0, -1.0),
+    (0.0, 1.0, 1.0), (0.0, -1.0, 1.0), (0.0, 1.0, -1.0), (0.0, -1.0, -1.0)
+];
+
+// Gradient noise (Perlin 2002), the same seed always builds the same permutation.
+#[derive(Clone)]
+pub struct Perlin {
+    permutation: Vec<usize>
+}
+
+impl Perlin {
+    pub fn new_default() -> Perlin {
+        Perlin::new(0)
+    }
+
+    pub fn new(seed: u64) -> Perlin {
+        let mut permutation: Vec<usize> = (0 .. PERMUTATION_SIZE).collect();
+        permutation.shuffle(&mut StdRng::seed_from_u64(seed));
+
+        // doubled so lookups of hash + 1 never wrap
+        let doubled = permutation.iter().chain(permutation.iter()).cloned().collect();
+        Perlin { permutation: doubled }
+    }
+
+    // Smooth noise in about [-1, 1], zero at every integer lattice point.
+    pub fn noise(&self, point: &Point3) -> f64 {
+        let floor = Vec3::new(point.x.floor(), point.y.floor(), point.z.floor());
+        let local = *point - floor;
+        let cell_x = (floor.x as i64).rem_euclid(PERMUTATION_SIZE as i64) as usize;
+        let cell_y = (floor.y as i64).rem_euclid(PERMUTATION_SIZE as i64) as usize;
+        let cell_z = (floor.z as i64).rem_euclid(PERMUTATION_SIZE as i64) as usize;
+
+        let fade_x = fade(local.x);
+        let fade_y = fade(local.y);
+        let fade_z = fade(local.z);
+
+        let mut corners = [0.0; 8];
+        for (index, corner) in corners.iter_mut().enumerate() {
+            let offset_x = index & 1;
+            let offset_y = (index >> 1) & 1;
+            let offset_z = (index >> 2) & 1;
+            let hash = self.hash(cell_x + offset_x, cell_y + offset_y, cell_z + offset_z);
+            let gradient = GRADIENTS[hash % GRADIENTS.len()];
+            let to_point = local - Vec3::new(offset_x as f64, offset_y as f64, offset_z as f64);
+            *corner = gradient.0 * to_point.x + gradient.1 * to_point.y + gradient.2 * to_point.z;
+        }
+
+        let x00 = lerp(corners[0], corners[1], fade_x);
+        let x10 = lerp(corners[2], corners[3], fade_x);
+        let x01 = lerp(corners[4], corners[5], fade_x);
+        let x11 = lerp(corners[6], corners[7], fade_x);
+        lerp(lerp(x00, x10, fade_y), lerp(x01, x11, fade_y), fade_z)
+    }
+
+    // Sum of octaves with halving amplitude and doubling frequency, in about [-1, 1].
+    pub fn fbm(&self, point: &Point3, octaves: u32) -> f64 {
+        let mut sum = 0.0;
+        let mut amplitude = 1.0;
+        let mut total_amplitude = 0.0;
+        let mut sample_point = *point;
+        for _ in 0 .. octaves.max(1) {
+            sum += amplitude * self.noise(&sample_point);
+            total_amplitude += amplitude;
+            amplitude *= 0.5;
+            sample_point *= 2.0;
+        }
+
+        sum / total_amplitude
+    }
+
+    // Like fbm but folds every octave to its absolute value, the billowy look of turbulence, in [0, 1].
+    pub fn turbulence(&self, point: &Point3, octaves: u32) -> f64 {
+        let mut sum = 0.0;
+        let mut amplitude = 1.0;
+        let mut total_amplitude = 0.0;
+        let mut sample_point = *point;
+        for _ in 0 .. octaves.max(1) {
+            sum += amplitude * self.noise(&sample_point).abs();
+            total_amplitude += amplitude;
+            amplitude *= 0.5;
+            sample_point *= 2.0;
+        }
+
+        (sum / total_amplitude).min(1.0)
+    }
+
+    fn hash(&self, x: usize, y: usize, z: usize) -> usize {
+        let permutation = &self.permutation;
+        permutation[permutation[permutation[x] + y] + z]
+    }
+}
+
+
+// Cellular noise (Worley 1996) with one feature point per unit cell.
+#[derive(Clone, Copy)]
+pub struct Worley {
+    seed: u64
+}
+
+impl Worley {
+    pub fn new_default() -> Worley {
+        Worley::new(0)
+    }
+
+    pub fn new(seed: u64) -> Worley {
+        Worley { seed }
+    }
+
+    // Distances to the closest and the second closest feature point.
+    pub fn distances(&self, point: &Point3) -> (f64, f64) {
+        let cell_x = point.x.floor() as i64;
+        let cell_y = point.y.floor() as i64;
+        let cell_z = point.z.floor() as i64;
+
+        let mut closest = f64::MAX;
+        let mut second_closest = f64::MAX;
+        for offset_z in -1 ..= 1 {
+            for offset_y in -1 ..= 1 {
+                for offset_x in -1 ..= 1 {
+                    let feature_point = self.get_feature_point(cell_x + offset_x, cell_y + offset_y, cell_z + offset_z);
+                    let distance = (feature_point - *point).length();
+                    if distance < closest {
+                        second_closest = closest;
+                        closest = distance;
+                    } else if distance < second_closest {
+                        second_closest = distance;
+                    }
+                }
+            }
+        }
+
+        (closest, second_closest)
+    }
+
+    fn get_feature_point(&self, x: i64, y: i64, z: i64) -> Point3 {
+        let mut hash = self.seed;
+        for coordinate in [x, y, z] {
+            hash = mix_bits(hash ^ coordinate as u64);
+        }
+
+        // three 21 bit fractions from one hash
+        let to_fraction = |bits: u64| (bits & 0x1f_ffff) as f64 / (1u64 << 21) as f64;
+        Point3::new(
+            x as f64 + to_fraction(hash),
+            y as f64 + to_fraction(hash >> 21),
+            z as f64 + to_fraction(hash >> 42))
+    }
+}
+
+fn fade(t: f64) -> f64 {
+    t * t * t * (t * (t * 6.0 - 15.0) + 10.0)
+}
+
+fn lerp(a: f64, b: f64, t: f64) -> f64 {
+    a + (b - a) * t
+}
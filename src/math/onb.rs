@@ -0,0 +1,50 @@
+
+use crate::math::vec3::Vec3;
+
+
+// Orthonormal basis around w, for turning locally sampled directions into world space.
+#[derive(Clone, Copy)]
+pub struct Onb {
+    pub u: Vec3,
+    pub v: Vec3,
+    pub w: Vec3
+}
+
+impl Onb {
+    pub fn new_from_w(w: &Vec3) -> Onb {
+        let w = w.get_normal();
+        // branchless basis from Duff et al. 2017
+        let sign = 1.0_f64.copysign(w.z);
+        let a = -1.0 / (sign + w.z);
+        let b = w.x * w.y * a;
+        let u = Vec3::new(1.0 + sign * w.x * w.x * a, sign * b, -sign * w.x);
+        let v = Vec3::new(b, sign + w.y * w.y * a, -w.y);
+        Onb { u, v, w }
+    }
+
+    // u follows the tangent as far as it is perpendicular to w, so the frame is tied to the surface
+    // parameterization. Falls back to new_from_w when the tangent is missing or parallel to w.
+    pub fn new_from_w_and_tangent(w: &Vec3, tangent: &Vec3) -> Onb {
+        let w = w.get_normal();
+        let u = *tangent - w * Vec3::dot(tangent, &w);
+        if u.sqaure_length() < 1e-12 {
+            return Onb::new_from_w(&w);
+        }
+
+        let u = u.get_normal();
+        let v = Vec3::cross(&w, &u);
+        Onb { u, v, w }
+    }
+
+    pub fn local(&self, x: f64, y: f64, z: f64) -> Vec3 {
+        self.u * x + self.v * y + self.w * z
+    }
+
+    pub fn local_to_world(&self, vector: &Vec3) -> Vec3 {
+        self.local(vector.x, vector.y, vector.z)
+    }
+
+    pub fn world_to_local(&self, vector: &Vec3) -> Vec3 {
+        Vec3::new(Vec3::dot(vector, &self.u), Vec3::dot(vector, &self.v), Vec3::dot(vector, &self.w))
+    }
+}
@@ -7,7 +7,7 @@ use std::ops::{
     Div, DivAssign
 };
 use std::clone::Clone;
-use rand::{thread_rng, Rng};
+use rand::Rng;
 use rand::distributions::Uniform;
 
 pub type Color = Vec3;
@@ -42,13 +42,11 @@ impl Vec3 {
             z: lhs.x * rhs.y - lhs.y * rhs.x }
     }
 
-    pub fn rand() -> Vec3 {
-        Vec3::rand_range((0.0, 1.0))
+    pub fn rand<R: Rng>(rng: &mut R) -> Vec3 {
+        Vec3::rand_range(rng, (0.0, 1.0))
     }
 
-    pub fn rand_range(range: (f64, f64)) -> Vec3 {
-        let mut rng = thread_rng();
-        let x = rng.sample(Uniform::new(10u32, 15));
+    pub fn rand_range<R: Rng>(rng: &mut R, range: (f64, f64)) -> Vec3 {
         Vec3 { 
             x: rng.sample(Uniform::new(range.0, range.1)), 
             y: rng.sample(Uniform::new(range.0, range.1)), 
@@ -56,39 +54,33 @@ impl Vec3 {
         }
     }
 
-    pub fn rand_in_unit_sphere() -> Vec3 {
-        loop {
-            let result = Vec3::rand_range((-1.0, 1.0));
-            if result.sqaure_length() <= 1.0 {
-                return result;
-            }
-        }
+    // Uniform direction from two uniform numbers, unlike rejection sampling it keeps the stratification of u.
+    pub fn sample_unit_vector(u: (f64, f64)) -> Vec3 {
+        let z = 1.0 - 2.0 * u.0;
+        let radius = (1.0 - z * z).max(0.0).sqrt();
+        let phi = 2.0 * std::f64::consts::PI * u.1;
+        Vec3::new(radius * phi.cos(), radius * phi.sin(), z)
     }
 
-    pub fn rand_in_hemisphere(normal: &Vec3) -> Vec3 {
-        let in_unit_sphere = Vec3::rand_in_unit_sphere();
-        if Vec3::dot(&in_unit_sphere, normal) > 0.0 {
-            return in_unit_sphere;
-        } else {
-            return in_unit_sphere;
-        }
+    pub fn sample_in_unit_sphere(u: (f64, f64), radius_u: f64) -> Vec3 {
+        Vec3::sample_unit_vector(u) * radius_u.cbrt()
     }
 
-    pub fn rand_in_unit_disk() -> Vec3 {
-        let mut rng = thread_rng();
-        let mut point: Vec3;
-        loop {
-            point = Vec3::new(
-                rng.sample(Uniform::new(-1.0, 1.0)),
-                rng.sample(Uniform::new(-1.0, 1.0)),
-                0.0);
-
-            if point.sqaure_length() < 1.0 {
-                break;
-            }
+    // Shirley's concentric mapping from the square to the disk, nearby points stay nearby.
+    pub fn sample_in_unit_disk(u: (f64, f64)) -> Vec3 {
+        let x = 2.0 * u.0 - 1.0;
+        let y = 2.0 * u.1 - 1.0;
+        if x == 0.0 && y == 0.0 {
+            return Vec3::new_default();
         }
 
-        point
+        let quarter_pi = std::f64::consts::FRAC_PI_4;
+        let (radius, theta) = if x.abs() > y.abs() {
+            (x, quarter_pi * (y / x))
+        } else {
+            (y, 2.0 * quarter_pi - quarter_pi * (x / y))
+        };
+        Vec3::new(radius * theta.cos(), radius * theta.sin(), 0.0)
     }
 
     pub fn length(&self) -> f64 {
@@ -117,7 +109,7 @@ impl Vec3 {
         let cos = Vec3::dot(&inv_self, normal).min(1.0);
         let refract_perp = (*self + (*normal * cos)) * eta;
         let refract_parallel = *normal * -(1.0 - refract_perp.sqaure_length()).abs().sqrt();
-        return refract_perp + refract_parallel;
+        refract_perp + refract_parallel
     }
 
     pub fn set_from_index(&mut self, index: usize, value: f64) {
@@ -131,12 +123,9 @@ impl Vec3 {
 
     pub fn is_near_zero(&self) -> bool {
         let epslion = 1e-8;
-        let result = 
-            self.x.abs() < epslion 
+        self.x.abs() < epslion 
             && self.y.abs() < epslion 
-            && self.z.abs() < epslion;
-
-        result
+            && self.z.abs() < epslion
     }
 }
 
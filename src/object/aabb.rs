@@ -0,0 +1,105 @@
+
+use crate::math::vec3::{Vec3, Point3};
+use crate::ray::Ray;
+
+
+#[derive(Clone, Copy)]
+pub struct Aabb {
+    pub min: Point3,
+    pub max: Point3
+}
+
+impl Aabb {
+    pub fn new_default() -> Aabb {
+        Aabb::new_empty()
+    }
+
+    pub fn new(min: Point3, max: Point3) -> Aabb {
+        Aabb { min, max }
+    }
+
+    pub fn new_empty() -> Aabb {
+        Aabb {
+            min: Point3::new(f64::INFINITY, f64::INFINITY, f64::INFINITY),
+            max: Point3::new(f64::NEG_INFINITY, f64::NEG_INFINITY, f64::NEG_INFINITY)
+        }
+    }
+
+    pub fn from_points(points: &[Point3]) -> Aabb {
+        let mut result = Aabb::new_empty();
+        for point in points {
+            result.expand_point(point);
+        }
+        result
+    }
+
+    pub fn surrounding(lhs: &Aabb, rhs: &Aabb) -> Aabb {
+        Aabb {
+            min: Point3::new(lhs.min.x.min(rhs.min.x), lhs.min.y.min(rhs.min.y), lhs.min.z.min(rhs.min.z)),
+            max: Point3::new(lhs.max.x.max(rhs.max.x), lhs.max.y.max(rhs.max.y), lhs.max.z.max(rhs.max.z))
+        }
+    }
+
+    pub fn expand(&mut self, other: &Aabb) {
+        *self = Aabb::surrounding(self, other);
+    }
+
+    pub fn expand_point(&mut self, point: &Point3) {
+        *self = Aabb::surrounding(self, &Aabb::new(*point, *point));
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.min.x > self.max.x || self.min.y > self.max.y || self.min.z > self.max.z
+    }
+
+    pub fn get_centroid(&self) -> Point3 {
+        (self.min + self.max) * 0.5
+    }
+
+    pub fn get_extent(&self) -> Vec3 {
+        self.max - self.min
+    }
+
+    pub fn get_largest_axis(&self) -> usize {
+        let extent = self.get_extent();
+        if extent.x > extent.y && extent.x > extent.z {
+            0
+        } else if extent.y > extent.z {
+            1
+        } else {
+            2
+        }
+    }
+
+    pub fn surface_area(&self) -> f64 {
+        if self.is_empty() {
+            return 0.0;
+        }
+
+        let extent = self.get_extent();
+        2.0 * (extent.x * extent.y + extent.y * extent.z + extent.z * extent.x)
+    }
+
+    // slab test, inv_direction is precomputed once per ray by the caller
+    pub fn hit(&self, ray: &Ray, inv_direction: &Vec3, weight_min: f64, weight_max: f64) -> bool {
+        let origin = ray.get_origin();
+        let mut t_min = weight_min;
+        let mut t_max = weight_max;
+        for axis in 0 .. 3 {
+            let mut t0 = (self.min[axis] - origin[axis]) * inv_direction[axis];
+            let mut t1 = (self.max[axis] - origin[axis]) * inv_direction[axis];
+            if inv_direction[axis] < 0.0 {
+                std::mem::swap(&mut t0, &mut t1);
+            }
+
+            // max/min order keeps NaN (0 * inf) from widening the interval
+            t_min = if t0 > t_min { t0 } else { t_min };
+            t_max = if t1 < t_max { t1 } else { t_max };
+            if t_max < t_min {
+                return false;
+            }
+        }
+
+        true
+    }
+}
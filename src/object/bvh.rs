@@ -0,0 +1,256 @@
+
+use crate::math::vec3::{Vec3, Point3};
+use crate::object::HitRecord;
+use crate::object::aabb::Aabb;
+use crate::ray::Ray;
+
+
+const SAH_BUCKET_COUNT: usize = 16;
+const MAX_LEAF_PRIMITIVES: usize = 4;
+const TRAVERSAL_COST: f64 = 1.0;
+const INTERSECT_COST: f64 = 1.0;
+
+#[derive(Clone, Copy)]
+enum BvhNode {
+    Leaf {
+        bounds: Aabb,
+        first: usize,
+        count: usize
+    },
+    Interior {
+        bounds: Aabb,
+        second_child: usize,
+        axis: usize
+    }
+}
+
+impl BvhNode {
+    fn get_bounds(&self) -> &Aabb {
+        match self {
+            BvhNode::Leaf { bounds, .. } => bounds,
+            BvhNode::Interior { bounds, .. } => bounds
+        }
+    }
+}
+
+struct BuildPrimitive {
+    index: usize,
+    bounds: Aabb,
+    centroid: Point3
+}
+
+#[derive(Clone, Copy)]
+struct SahBucket {
+    count: usize,
+    bounds: Aabb
+}
+
+// Flattened bounding volume hierarchy over an external primitive list.
+// The first child of an interior node is always stored right after it.
+#[derive(Clone)]
+pub struct Bvh {
+    nodes: Vec<BvhNode>,
+    primitive_indices: Vec<usize>
+}
+
+impl Bvh {
+    pub fn new_default() -> Bvh {
+        Bvh {
+            nodes: Vec::new(),
+            primitive_indices: Vec::new()
+        }
+    }
+
+    pub fn new(primitive_bounds: &[Aabb]) -> Bvh {
+        let mut build_primitives: Vec<BuildPrimitive> = primitive_bounds.iter()
+            .enumerate()
+            .map(|(index, bounds)| BuildPrimitive { index, bounds: *bounds, centroid: bounds.get_centroid() })
+            .collect();
+
+        let mut bvh = Bvh::new_default();
+        if !build_primitives.is_empty() {
+            bvh.nodes.reserve(build_primitives.len() * 2);
+            bvh.build_recursive(&mut build_primitives);
+        }
+        bvh
+    }
+
+    pub fn get_bounds(&self) -> Aabb {
+        match self.nodes.first() {
+            Some(node) => *node.get_bounds(),
+            None => Aabb::new_empty()
+        }
+    }
+
+    pub fn get_node_count(&self) -> usize {
+        self.nodes.len()
+    }
+
+    // Walks the tree front to back, calling hit_primitive with the index of each candidate primitive.
+    pub fn traverse<'a, F>(&self, ray: &Ray, weight_min: f64, weight_max: f64, mut hit_primitive: F) -> Result<HitRecord<'a>, ()>
+        where F: FnMut(usize, &Ray, f64, f64) -> Result<HitRecord<'a>, ()>
+    {
+        if self.nodes.is_empty() {
+            return Err(());
+        }
+
+        let direction = ray.get_direction();
+        let inv_direction = Vec3::new(1.0 / direction.x, 1.0 / direction.y, 1.0 / direction.z);
+        let is_negative = [inv_direction.x < 0.0, inv_direction.y < 0.0, inv_direction.z < 0.0];
+
+        let mut closest_so_far = weight_max;
+        let mut hit_record: Option<HitRecord<'a>> = None;
+
+        let mut stack: Vec<usize> = Vec::with_capacity(64);
+        stack.push(0);
+        while let Some(node_index) = stack.pop() {
+            let node = &self.nodes[node_index];
+            if !node.get_bounds().hit(ray, &inv_direction, weight_min, closest_so_far) {
+                continue;
+            }
+
+            match node {
+                BvhNode::Leaf { first, count, .. } => {
+                    for i in *first .. *first + *count {
+                        let primitive_index = self.primitive_indices[i];
+                        if let Ok(record) = hit_primitive(primitive_index, ray, weight_min, closest_so_far) {
+                            closest_so_far = record.weight;
+                            hit_record = Some(record);
+                        }
+                    }
+                }
+                BvhNode::Interior { second_child, axis, .. } => {
+                    // push the far child first so the near one is visited first
+                    if is_negative[*axis] {
+                        stack.push(node_index + 1);
+                        stack.push(*second_child);
+                    } else {
+                        stack.push(*second_child);
+                        stack.push(node_index + 1);
+                    }
+                }
+            }
+        }
+
+        match hit_record {
+            Some(record) => Ok(record),
+            None => Err(())
+        }
+    }
+
+    fn build_recursive(&mut self, primitives: &mut [BuildPrimitive]) -> usize {
+        let mut bounds = Aabb::new_empty();
+        let mut centroid_bounds = Aabb::new_empty();
+        for primitive in primitives.iter() {
+            bounds.expand(&primitive.bounds);
+            centroid_bounds.expand_point(&primitive.centroid);
+        }
+
+        let node_index = self.nodes.len();
+        if primitives.len() <= MAX_LEAF_PRIMITIVES {
+            self.push_leaf(bounds, primitives);
+            return node_index;
+        }
+
+        let axis = centroid_bounds.get_largest_axis();
+        let axis_min = centroid_bounds.min[axis];
+        let axis_extent = centroid_bounds.max[axis] - axis_min;
+        if axis_extent <= 0.0 {
+            // every centroid is at the same spot, no split can separate them
+            self.push_leaf(bounds, primitives);
+            return node_index;
+        }
+
+        let mut buckets = [SahBucket { count: 0, bounds: Aabb::new_empty() }; SAH_BUCKET_COUNT];
+        let bucket_of = |centroid: &Point3| -> usize {
+            let offset = (centroid[axis] - axis_min) / axis_extent;
+            ((offset * SAH_BUCKET_COUNT as f64) as usize).min(SAH_BUCKET_COUNT - 1)
+        };
+
+        for primitive in primitives.iter() {
+            let bucket = &mut buckets[bucket_of(&primitive.centroid)];
+            bucket.count += 1;
+            bucket.bounds.expand(&primitive.bounds);
+        }
+
+        // sweep from the right to get the cost of every split plane in linear time
+        let mut right_area = [0.0; SAH_BUCKET_COUNT];
+        let mut right_count = [0usize; SAH_BUCKET_COUNT];
+        let mut accumulated_bounds = Aabb::new_empty();
+        let mut accumulated_count = 0;
+        for i in (1 .. SAH_BUCKET_COUNT).rev() {
+            accumulated_bounds.expand(&buckets[i].bounds);
+            accumulated_count += buckets[i].count;
+            right_area[i] = accumulated_bounds.surface_area();
+            right_count[i] = accumulated_count;
+        }
+
+        let mut best_split = 0;
+        let mut best_cost = f64::INFINITY;
+        accumulated_bounds = Aabb::new_empty();
+        accumulated_count = 0;
+        for i in 0 .. SAH_BUCKET_COUNT - 1 {
+            accumulated_bounds.expand(&buckets[i].bounds);
+            accumulated_count += buckets[i].count;
+            let cost =
+                accumulated_count as f64 * accumulated_bounds.surface_area() +
+                right_count[i + 1] as f64 * right_area[i + 1];
+            if cost < best_cost {
+                best_cost = cost;
+                best_split = i;
+            }
+        }
+
+        let parent_area = bounds.surface_area();
+        let split_cost = if parent_area > 0.0 {
+            TRAVERSAL_COST + INTERSECT_COST * best_cost / parent_area
+        } else {
+            TRAVERSAL_COST
+        };
+        let leaf_cost = INTERSECT_COST * primitives.len() as f64;
+
+        if split_cost >= leaf_cost && primitives.len() <= MAX_LEAF_PRIMITIVES * 4 {
+            self.push_leaf(bounds, primitives);
+            return node_index;
+        }
+
+        let mut middle = partition(primitives, |primitive| bucket_of(&primitive.centroid) <= best_split);
+        if middle == 0 || middle == primitives.len() {
+            // SAH could not separate the primitives, fall back to a median split
+            primitives.sort_by(|lhs, rhs| lhs.centroid[axis].total_cmp(&rhs.centroid[axis]));
+            middle = primitives.len() / 2;
+        }
+
+        self.nodes.push(BvhNode::Interior { bounds, second_child: 0, axis });
+        let (left, right) = primitives.split_at_mut(middle);
+        self.build_recursive(left);
+        let right_index = self.build_recursive(right);
+        if let BvhNode::Interior { second_child, .. } = &mut self.nodes[node_index] {
+            *second_child = right_index;
+        }
+
+        node_index
+    }
+
+    fn push_leaf(&mut self, bounds: Aabb, primitives: &[BuildPrimitive]) {
+        let first = self.primitive_indices.len();
+        for primitive in primitives {
+            self.primitive_indices.push(primitive.index);
+        }
+
+        self.nodes.push(BvhNode::Leaf { bounds, first, count: primitives.len() });
+    }
+}
+
+fn partition<T, F>(items: &mut [T], predicate: F) -> usize
+    where F: Fn(&T) -> bool
+{
+    let mut middle = 0;
+    for i in 0 .. items.len() {
+        if predicate(&items[i]) {
+            items.swap(i, middle);
+            middle += 1;
+        }
+    }
+    middle
+}
@@ -1,31 +1,91 @@
 
+pub mod aabb;
+pub mod bvh;
 pub mod sphere;
+pub mod triangle;
+pub mod triangle_mesh;
 
-use dyn_clone::DynClone;
+#[cfg(test)]
+mod tests;
 
 use crate::math::vec3::{Vec3, Point3};
 use crate::ray::Ray;
 use crate::material::Material;
+use crate::object::aabb::Aabb;
+use crate::sampler::Sampler;
 
 pub struct HitRecord<'a> {
     pub point: Point3,
     pub normal: Vec3,
     pub weight: f64,
+    pub uv: (f64, f64),
+    // unit direction in which u grows along the surface (dp/du), zero where the surface has none
+    pub tangent: Vec3,
+    // width of the ray cone at the hit measured in uv units, zero reads the finest texture detail
+    pub uv_footprint: f64,
     pub is_front_face: bool,
+    // set by the world, one more than the index of the object so zero means none
+    pub object_id: u32,
     pub material : &'a dyn Material
 }
 
 impl<'a> HitRecord<'a> {
     pub fn set_face_from_ray(&mut self, ray: &Ray) {
         self.is_front_face = Vec3::dot(ray.get_direction(), &self.normal) < 0.0;
-        if self.is_front_face == false {
+        if !self.is_front_face {
             self.normal = -self.normal;
         }
     }
+
+    // uv_density is how many uv units one world unit covers on the surface around the hit.
+    pub fn set_footprint_from_ray(&mut self, ray: &Ray, uv_density: f64) {
+        let direction_length = ray.get_direction().length();
+        if direction_length <= 0.0 {
+            return;
+        }
+
+        // the footprint stretches as the surface turns away from the ray
+        let cos = (Vec3::dot(ray.get_direction(), &self.normal) / direction_length).abs().max(0.01);
+        self.uv_footprint = ray.get_cone_width(self.weight) / cos * uv_density;
+    }
 }
 
-pub trait Hittable: Send + DynClone {
-    fn hit(&self, ray: &Ray, weight_min: f64, weight_max: f64) -> Result<HitRecord, ()>;
+// A point picked on a shape as seen from some origin, pdf is with respect to solid angle at that origin.
+#[derive(Clone, Copy)]
+pub struct ShapeSample {
+    pub point: Point3,
+    pub normal: Vec3,
+    pub pdf: f64
 }
 
-dyn_clone::clone_trait_object!(Hittable);
+// Shared by every render thread, so implementations are immutable once the world is built.
+pub trait Hittable: Send + Sync {
+    fn hit(&self, ray: &Ray, weight_min: f64, weight_max: f64) -> Result<HitRecord<'_>, ()>;
+    fn bounding_box(&self) -> Aabb;
+
+    fn is_emissive(&self) -> bool {
+        false
+    }
+
+    // Emissive shapes that can not be sampled are still found by scattered rays, just with more noise.
+    fn sample_point(&self, origin: &Point3, sampler: &mut dyn Sampler) -> Option<ShapeSample> {
+        None
+    }
+
+    // pdf with which sample_point would have picked the first point hit along direction
+    fn pdf_value(&self, origin: &Point3, direction: &Vec3) -> f64 {
+        0.0
+    }
+}
+
+// Converts a pdf with respect to surface area into one with respect to solid angle seen from origin.
+pub fn area_to_solid_angle_pdf(area_pdf: f64, origin: &Point3, point: &Point3, normal: &Vec3) -> f64 {
+    let to_point = *point - *origin;
+    let distance_squared = to_point.sqaure_length();
+    let cos = Vec3::dot(normal, &to_point).abs() / distance_squared.sqrt();
+    if cos <= 1e-8 {
+        return 0.0;
+    }
+
+    area_pdf * distance_squared / cos
+}
@@ -1,9 +1,14 @@
 
+use std::f64::consts::PI;
+
 use crate::material::Material;
 use crate::material::errormat::ErrorMat;
-use crate::object::{Hittable, HitRecord};
+use crate::object::{Hittable, HitRecord, ShapeSample, area_to_solid_angle_pdf};
+use crate::object::aabb::Aabb;
 use crate::math::vec3::{Vec3, Point3};
+use crate::math::onb::Onb;
 use crate::ray::Ray;
+use crate::sampler::Sampler;
 
 
 #[derive(Clone)]
@@ -28,10 +33,37 @@ impl Sphere {
             material
         }
     }
+
+    // cosine of the half angle of the cone the sphere covers seen from origin, None from inside
+    fn get_cone_cos(&self, origin: &Point3) -> Option<f64> {
+        let distance_squared = (self.center - *origin).sqaure_length();
+        let radius_squared = self.radius * self.radius;
+        if distance_squared <= radius_squared {
+            return None;
+        }
+
+        Some((1.0 - radius_squared / distance_squared).max(0.0).sqrt())
+    }
+}
+
+// u goes around the y axis starting at -x, v from the bottom pole to the top one.
+pub fn get_sphere_uv(outward_normal: &Vec3) -> (f64, f64) {
+    let theta = (-outward_normal.y).clamp(-1.0, 1.0).acos();
+    let phi = (-outward_normal.z).atan2(outward_normal.x) + PI;
+    (phi / (2.0 * PI), theta / PI)
+}
+
+// u runs around the y axis, the tangent is zero at the poles where it has no direction
+pub fn get_sphere_tangent(to_point: &Vec3) -> Vec3 {
+    let tangent = Vec3::new(to_point.z, 0.0, -to_point.x);
+    if tangent.is_near_zero() {
+        return Vec3::new_default();
+    }
+    tangent.get_normal()
 }
 
 impl Hittable for Sphere {
-    fn hit(&self, ray: &Ray, weight_min: f64, weight_max: f64) -> Result<HitRecord, ()>
+    fn hit(&self, ray: &Ray, weight_min: f64, weight_max: f64) -> Result<HitRecord<'_>, ()>
     {
         let to_ray_origin = *ray.get_origin() - self.center;
         let value_a = ray.get_direction().sqaure_length();
@@ -52,15 +84,80 @@ impl Hittable for Sphere {
         }
 
         let hit_point = ray.get_point(root);
+        let outward_normal = (hit_point - self.center) / self.radius;
         let mut record = HitRecord {
             point: hit_point,
-            normal: (hit_point - self.center) / self.radius,
+            normal: outward_normal,
             weight: root,
+            uv: get_sphere_uv(&outward_normal),
+            tangent: get_sphere_tangent(&(hit_point - self.center)),
+            uv_footprint: 0.0,
             is_front_face: true,
+            object_id: 0,
             material: &*self.material
         };
         record.set_face_from_ray(ray);
+        // the uv square is stretched over the whole surface
+        record.set_footprint_from_ray(ray, 1.0 / (2.0 * self.radius.abs() * PI.sqrt()));
 
         Ok(record)
     }
-}
\ No newline at end of file
+
+    fn bounding_box(&self) -> Aabb {
+        let radius = Vec3::new(self.radius.abs(), self.radius.abs(), self.radius.abs());
+        Aabb::new(self.center - radius, self.center + radius)
+    }
+
+    fn is_emissive(&self) -> bool {
+        self.material.is_emissive()
+    }
+
+    fn sample_point(&self, origin: &Point3, sampler: &mut dyn Sampler) -> Option<ShapeSample> {
+        let u = sampler.get_2d();
+        match self.get_cone_cos(origin) {
+            Some(cos_max) => {
+                // uniform direction inside the cone, then find where it meets the sphere
+                let cos_theta = 1.0 + u.0 * (cos_max - 1.0);
+                let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+                let phi = 2.0 * PI * u.1;
+                let onb = Onb::new_from_w(&(self.center - *origin));
+                let direction = onb.local(phi.cos() * sin_theta, phi.sin() * sin_theta, cos_theta);
+
+                let record = self.hit(&Ray::new(*origin, direction), 0.0, f64::MAX).ok()?;
+                Some(ShapeSample {
+                    point: record.point,
+                    normal: (record.point - self.center) / self.radius,
+                    pdf: 1.0 / (2.0 * PI * (1.0 - cos_max))
+                })
+            }
+            None => {
+                // inside the sphere every point is visible, sample the whole surface
+                let normal = Vec3::sample_unit_vector(u);
+                let point = self.center + normal * self.radius;
+                let area = 4.0 * PI * self.radius * self.radius;
+                let pdf = area_to_solid_angle_pdf(1.0 / area, origin, &point, &normal);
+                if pdf <= 0.0 {
+                    return None;
+                }
+
+                Some(ShapeSample { point, normal, pdf })
+            }
+        }
+    }
+
+    fn pdf_value(&self, origin: &Point3, direction: &Vec3) -> f64 {
+        let record = match self.hit(&Ray::new(*origin, *direction), 0.0001, f64::MAX) {
+            Ok(record) => record,
+            Err(()) => return 0.0
+        };
+
+        match self.get_cone_cos(origin) {
+            Some(cos_max) => 1.0 / (2.0 * PI * (1.0 - cos_max)),
+            None => {
+                let area = 4.0 * PI * self.radius * self.radius;
+                let normal = (record.point - self.center) / self.radius;
+                area_to_solid_angle_pdf(1.0 / area, origin, &record.point, &normal)
+            }
+        }
+    }
+}
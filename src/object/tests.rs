@@ -0,0 +1,104 @@
+
+// Intersection checks: rays through edges and vertices shared by triangles hit exactly one of them, however
+// the ray is sheared, and the BVH finds the same closest hit as testing every primitive.
+
+use rand::{Rng, SeedableRng};
+use rand::rngs::StdRng;
+
+use crate::material::errormat::ErrorMat;
+use crate::math::vec3::{Vec3, Point3};
+use crate::object::Hittable;
+use crate::object::aabb::Aabb;
+use crate::object::bvh::Bvh;
+use crate::object::sphere::Sphere;
+use crate::object::triangle::intersect_triangle;
+use crate::ray::Ray;
+
+
+const RAY_COUNT: usize = 2000;
+
+
+fn get_random_origin(rng: &mut StdRng) -> Point3 {
+    let origin = Point3::new(rng.gen_range(-3.0 .. 3.0), rng.gen_range(-3.0 .. 3.0), rng.gen_range(0.5 .. 3.0));
+    // from below as well, so both windings are seen
+    if rng.gen_bool(0.5) { origin } else { Point3::new(origin.x, origin.y, -origin.z) }
+}
+
+// Number of triangles the ray towards target hits.
+fn count_hits(triangles: &[[Point3; 3]], origin: &Point3, target: &Point3) -> usize {
+    let ray = Ray::new(*origin, *target - *origin);
+    triangles.iter()
+        .filter(|triangle| intersect_triangle(&ray, [&triangle[0], &triangle[1], &triangle[2]], 0.0, f64::MAX).is_some())
+        .count()
+}
+
+#[test]
+fn shared_edge_is_hit_exactly_once() {
+    let corners = [Point3::new(0.0, 0.0, 0.0), Point3::new(1.0, 0.0, 0.0), Point3::new(1.0, 1.0, 0.0), Point3::new(0.0, 1.0, 0.0)];
+    let consistent = [[corners[0], corners[1], corners[2]], [corners[0], corners[2], corners[3]]];
+    let flipped = [[corners[0], corners[1], corners[2]], [corners[0], corners[3], corners[2]]];
+
+    let mut rng = StdRng::seed_from_u64(1);
+    for triangles in [consistent, flipped] {
+        // straight down onto points of the diagonal, the edge functions are exactly zero there
+        for step in 1 .. 16 {
+            let target = Point3::new(step as f64 / 16.0, step as f64 / 16.0, 0.0);
+            let origin = target + Vec3::new(0.0, 0.0, 1.0);
+            assert_eq!(count_hits(&triangles, &origin, &target), 1, "straight ray onto the shared edge at {}", step);
+        }
+
+        for _ in 0 .. RAY_COUNT {
+            let t = rng.gen_range(0.0 .. 1.0);
+            let target = Point3::new(t, t, 0.0);
+            let origin = get_random_origin(&mut rng);
+            assert_eq!(count_hits(&triangles, &origin, &target), 1, "sheared ray onto the shared edge at {}", t);
+        }
+    }
+}
+
+#[test]
+fn shared_vertex_is_hit_exactly_once() {
+    let center = Point3::new(0.0, 0.0, 0.0);
+    let rim: Vec<Point3> = (0 .. 6)
+        .map(|index| {
+            let angle = index as f64 * std::f64::consts::PI / 3.0;
+            Point3::new(angle.cos(), angle.sin(), 0.0)
+        })
+        .collect();
+    let fan: Vec<[Point3; 3]> = (0 .. 6).map(|index| [center, rim[index], rim[(index + 1) % 6]]).collect();
+
+    let mut rng = StdRng::seed_from_u64(2);
+    assert_eq!(count_hits(&fan, &Point3::new(0.0, 0.0, 1.0), &center), 1, "straight ray onto the shared vertex");
+    for _ in 0 .. RAY_COUNT {
+        let origin = get_random_origin(&mut rng);
+        assert_eq!(count_hits(&fan, &origin, &center), 1, "ray from {} {} {} onto the shared vertex", origin.x, origin.y, origin.z);
+    }
+}
+
+#[test]
+fn bvh_finds_the_closest_hit() {
+    let mut rng = StdRng::seed_from_u64(3);
+    let spheres: Vec<Sphere> = (0 .. 200)
+        .map(|_| {
+            let center = Point3::new(rng.gen_range(-10.0 .. 10.0), rng.gen_range(-10.0 .. 10.0), rng.gen_range(-10.0 .. 10.0));
+            Sphere::new(center, rng.gen_range(0.1 .. 1.5), Box::new(ErrorMat::new_default()))
+        })
+        .collect();
+    let bounds: Vec<Aabb> = spheres.iter().map(|sphere| sphere.bounding_box()).collect();
+    let bvh = Bvh::new(&bounds);
+
+    for _ in 0 .. RAY_COUNT {
+        let origin = Point3::new(rng.gen_range(-15.0 .. 15.0), rng.gen_range(-15.0 .. 15.0), rng.gen_range(-15.0 .. 15.0));
+        let target = Point3::new(rng.gen_range(-10.0 .. 10.0), rng.gen_range(-10.0 .. 10.0), rng.gen_range(-10.0 .. 10.0));
+        let ray = Ray::new(origin, target - origin);
+
+        let expected = spheres.iter()
+            .filter_map(|sphere| sphere.hit(&ray, 0.001, f64::MAX).ok())
+            .map(|record| record.weight)
+            .fold(f64::MAX, f64::min);
+        let found = bvh.traverse(&ray, 0.001, f64::MAX, |index, ray, weight_min, weight_max| spheres[index].hit(ray, weight_min, weight_max))
+            .map(|record| record.weight)
+            .unwrap_or(f64::MAX);
+        assert_eq!(found, expected, "bvh and brute force disagree");
+    }
+}
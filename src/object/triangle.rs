@@ -0,0 +1,269 @@
+
+use crate::material::Material;
+use crate::material::errormat::ErrorMat;
+use crate::object::{Hittable, HitRecord, ShapeSample, area_to_solid_angle_pdf};
+use crate::object::aabb::Aabb;
+use crate::math::vec3::{Vec3, Point3};
+use crate::ray::Ray;
+use crate::sampler::Sampler;
+
+
+pub const DEFAULT_TRIANGLE_UVS: [(f64, f64); 3] = [(0.0, 0.0), (1.0, 0.0), (1.0, 1.0)];
+
+#[derive(Clone, Copy)]
+pub struct TriangleHit {
+    pub weight: f64,
+    pub barycentric: [f64; 3]
+}
+
+// Watertight ray/triangle intersection (Woop, Benthin and Wald 2013).
+// Rays passing exactly through an edge or vertex shared by adjacent triangles hit exactly one of them.
+pub fn intersect_triangle(ray: &Ray, vertices: [&Point3; 3], weight_min: f64, weight_max: f64) -> Option<TriangleHit> {
+    let direction = ray.get_direction();
+    let origin = ray.get_origin();
+
+    // permute axes so the ray direction is dominant in z
+    let abs_direction = [direction.x.abs(), direction.y.abs(), direction.z.abs()];
+    let axis_z = if abs_direction[0] > abs_direction[1] && abs_direction[0] > abs_direction[2] {
+        0
+    } else if abs_direction[1] > abs_direction[2] {
+        1
+    } else {
+        2
+    };
+
+    let mut axis_x = (axis_z + 1) % 3;
+    let mut axis_y = (axis_x + 1) % 3;
+    if direction[axis_z] < 0.0 {
+        std::mem::swap(&mut axis_x, &mut axis_y);
+    }
+
+    if direction[axis_z] == 0.0 {
+        return None;
+    }
+
+    let shear_x = direction[axis_x] / direction[axis_z];
+    let shear_y = direction[axis_y] / direction[axis_z];
+    let shear_z = 1.0 / direction[axis_z];
+
+    let a = *vertices[0] - *origin;
+    let b = *vertices[1] - *origin;
+    let c = *vertices[2] - *origin;
+
+    let a_x = a[axis_x] - shear_x * a[axis_z];
+    let a_y = a[axis_y] - shear_y * a[axis_z];
+    let b_x = b[axis_x] - shear_x * b[axis_z];
+    let b_y = b[axis_y] - shear_y * b[axis_z];
+    let c_x = c[axis_x] - shear_x * c[axis_z];
+    let c_y = c[axis_y] - shear_y * c[axis_z];
+
+    // scaled barycentric coordinates from edge functions
+    let u = c_x * b_y - c_y * b_x;
+    let v = a_x * c_y - a_y * c_x;
+    let w = b_x * a_y - b_y * a_x;
+
+    if (u < 0.0 || v < 0.0 || w < 0.0) && (u > 0.0 || v > 0.0 || w > 0.0) {
+        return None;
+    }
+
+    let determinant = u + v + w;
+    if determinant == 0.0 {
+        return None;
+    }
+
+    // A ray exactly on an edge would hit both triangles sharing it. The edge belongs to the triangle it runs
+    // up (or right, when level) in once both are turned counterclockwise, which the neighbor sees reversed.
+    let orientation = determinant.signum();
+    let is_owned = |edge_function: f64, from: (f64, f64), to: (f64, f64)| {
+        let direction = ((to.0 - from.0) * orientation, (to.1 - from.1) * orientation);
+        edge_function != 0.0 || direction.1 > 0.0 || (direction.1 == 0.0 && direction.0 > 0.0)
+    };
+    if !is_owned(u, (b_x, b_y), (c_x, c_y)) || !is_owned(v, (c_x, c_y), (a_x, a_y)) || !is_owned(w, (a_x, a_y), (b_x, b_y)) {
+        return None;
+    }
+
+    let scaled_weight =
+        u * shear_z * a[axis_z] +
+        v * shear_z * b[axis_z] +
+        w * shear_z * c[axis_z];
+
+    let inv_determinant = 1.0 / determinant;
+    let weight = scaled_weight * inv_determinant;
+    if weight < weight_min || weight > weight_max {
+        return None;
+    }
+
+    Some(TriangleHit {
+        weight,
+        barycentric: [u * inv_determinant, v * inv_determinant, w * inv_determinant]
+    })
+}
+
+pub fn make_triangle_record<'a>(
+    ray: &Ray,
+    hit: &TriangleHit,
+    vertices: [&Point3; 3],
+    normals: Option<[&Vec3; 3]>,
+    uvs: [(f64, f64); 3],
+    material: &'a dyn Material) -> HitRecord<'a>
+{
+    let barycentric = hit.barycentric;
+    let geometric_normal = Vec3::cross(&(*vertices[1] - *vertices[0]), &(*vertices[2] - *vertices[0])).get_normal();
+
+    // shading normal from the vertex normals, the face is still decided by the real geometry
+    let shading_normal = match normals {
+        Some(normals) => {
+            let interpolated = *normals[0] * barycentric[0] + *normals[1] * barycentric[1] + *normals[2] * barycentric[2];
+            if interpolated.is_near_zero() {
+                geometric_normal
+            } else {
+                let interpolated = interpolated.get_normal();
+                if Vec3::dot(&interpolated, &geometric_normal) < 0.0 { -interpolated } else { interpolated }
+            }
+        }
+        None => geometric_normal
+    };
+
+    let uv = (
+        uvs[0].0 * barycentric[0] + uvs[1].0 * barycentric[1] + uvs[2].0 * barycentric[2],
+        uvs[0].1 * barycentric[0] + uvs[1].1 * barycentric[1] + uvs[2].1 * barycentric[2]
+    );
+
+    let is_front_face = Vec3::dot(ray.get_direction(), &geometric_normal) < 0.0;
+    let mut record = HitRecord {
+        point: ray.get_point(hit.weight),
+        normal: if is_front_face { shading_normal } else { -shading_normal },
+        weight: hit.weight,
+        uv,
+        tangent: get_triangle_tangent(vertices, uvs),
+        uv_footprint: 0.0,
+        is_front_face,
+        object_id: 0,
+        material
+    };
+
+    let uv_area = ((uvs[1].0 - uvs[0].0) * (uvs[2].1 - uvs[0].1) - (uvs[2].0 - uvs[0].0) * (uvs[1].1 - uvs[0].1)).abs() * 0.5;
+    let area = get_triangle_area(vertices);
+    if area > 0.0 {
+        record.set_footprint_from_ray(ray, (uv_area / area).sqrt());
+    }
+
+    record
+}
+
+// dp/du from the uvs of the corners, the first edge stands in when the uvs don't span an area
+pub fn get_triangle_tangent(vertices: [&Point3; 3], uvs: [(f64, f64); 3]) -> Vec3 {
+    let edge_02 = *vertices[0] - *vertices[2];
+    let edge_12 = *vertices[1] - *vertices[2];
+    let uv_02 = (uvs[0].0 - uvs[2].0, uvs[0].1 - uvs[2].1);
+    let uv_12 = (uvs[1].0 - uvs[2].0, uvs[1].1 - uvs[2].1);
+    let determinant = uv_02.0 * uv_12.1 - uv_02.1 * uv_12.0;
+
+    let tangent = match determinant.abs() > 1e-12 {
+        true => (edge_02 * uv_12.1 - edge_12 * uv_02.1) / determinant,
+        false => *vertices[1] - *vertices[0]
+    };
+    if tangent.is_near_zero() {
+        return Vec3::new_default();
+    }
+    tangent.get_normal()
+}
+
+pub fn get_triangle_area(vertices: [&Point3; 3]) -> f64 {
+    Vec3::cross(&(*vertices[1] - *vertices[0]), &(*vertices[2] - *vertices[0])).length() * 0.5
+}
+
+// Uniformly distributed point on the triangle from two uniform numbers, with its geometric normal.
+pub fn sample_triangle(vertices: [&Point3; 3], u: f64, v: f64) -> (Point3, Vec3) {
+    let sqrt_u = u.sqrt();
+    let b0 = 1.0 - sqrt_u;
+    let b1 = v * sqrt_u;
+    let point = *vertices[0] * b0 + *vertices[1] * b1 + *vertices[2] * (1.0 - b0 - b1);
+    let normal = Vec3::cross(&(*vertices[1] - *vertices[0]), &(*vertices[2] - *vertices[0])).get_normal();
+    (point, normal)
+}
+
+
+#[derive(Clone)]
+pub struct Triangle {
+    vertices: [Point3; 3],
+    normals: Option<[Vec3; 3]>,
+    uvs: [(f64, f64); 3],
+    material: Box<dyn Material>
+}
+
+impl Triangle {
+    pub fn new_default() -> Triangle {
+        Triangle::new(
+            [Point3::new_default(), Point3::new_default(), Point3::new_default()],
+            Box::new(ErrorMat::new_default()))
+    }
+
+    pub fn new(vertices: [Point3; 3], material: Box<dyn Material>) -> Triangle {
+        Triangle::new_with_attributes(vertices, None, None, material)
+    }
+
+    pub fn new_with_attributes(vertices: [Point3; 3], normals: Option<[Vec3; 3]>, uvs: Option<[(f64, f64); 3]>, material: Box<dyn Material>) -> Triangle {
+        let normals = normals.map(|normals| [normals[0].get_normal(), normals[1].get_normal(), normals[2].get_normal()]);
+        Triangle {
+            vertices,
+            normals,
+            uvs: uvs.unwrap_or(DEFAULT_TRIANGLE_UVS),
+            material
+        }
+    }
+
+    pub fn get_vertices(&self) -> &[Point3; 3] {
+        &self.vertices
+    }
+}
+
+impl Hittable for Triangle {
+    fn hit(&self, ray: &Ray, weight_min: f64, weight_max: f64) -> Result<HitRecord<'_>, ()> {
+        let vertices = [&self.vertices[0], &self.vertices[1], &self.vertices[2]];
+        match intersect_triangle(ray, vertices, weight_min, weight_max) {
+            Some(hit) => {
+                let normals = self.normals.as_ref().map(|normals| [&normals[0], &normals[1], &normals[2]]);
+                Ok(make_triangle_record(ray, &hit, vertices, normals, self.uvs, &*self.material))
+            }
+            None => Err(())
+        }
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        Aabb::from_points(&self.vertices)
+    }
+
+    fn is_emissive(&self) -> bool {
+        self.material.is_emissive()
+    }
+
+    fn sample_point(&self, origin: &Point3, sampler: &mut dyn Sampler) -> Option<ShapeSample> {
+        let vertices = [&self.vertices[0], &self.vertices[1], &self.vertices[2]];
+        let area = get_triangle_area(vertices);
+        if area <= 0.0 {
+            return None;
+        }
+
+        let u = sampler.get_2d();
+        let (point, normal) = sample_triangle(vertices, u.0, u.1);
+        let pdf = area_to_solid_angle_pdf(1.0 / area, origin, &point, &normal);
+        if pdf <= 0.0 {
+            return None;
+        }
+
+        Some(ShapeSample { point, normal, pdf })
+    }
+
+    fn pdf_value(&self, origin: &Point3, direction: &Vec3) -> f64 {
+        let vertices = [&self.vertices[0], &self.vertices[1], &self.vertices[2]];
+        match intersect_triangle(&Ray::new(*origin, *direction), vertices, 0.0001, f64::MAX) {
+            Some(hit) => {
+                let point = *origin + *direction * hit.weight;
+                let normal = Vec3::cross(&(*vertices[1] - *vertices[0]), &(*vertices[2] - *vertices[0])).get_normal();
+                area_to_solid_angle_pdf(1.0 / get_triangle_area(vertices), origin, &point, &normal)
+            }
+            None => 0.0
+        }
+    }
+}
@@ -0,0 +1,195 @@
+
+use std::sync::Arc;
+
+use crate::material::Material;
+use crate::object::{Hittable, HitRecord, ShapeSample, area_to_solid_angle_pdf};
+use crate::object::aabb::Aabb;
+use crate::object::bvh::Bvh;
+use crate::object::triangle::{intersect_triangle, make_triangle_record, get_triangle_area, sample_triangle, DEFAULT_TRIANGLE_UVS};
+use crate::math::vec3::{Vec3, Point3};
+use crate::ray::Ray;
+use crate::sampler::Sampler;
+
+
+// Indices into the shared attribute arrays of a TriangleMesh.
+#[derive(Clone, Copy)]
+pub struct MeshFace {
+    pub vertices: [usize; 3],
+    pub normals: Option<[usize; 3]>,
+    pub uvs: Option<[usize; 3]>
+}
+
+impl MeshFace {
+    pub fn new(vertices: [usize; 3]) -> MeshFace {
+        MeshFace {
+            vertices,
+            normals: None,
+            uvs: None
+        }
+    }
+}
+
+struct MeshData {
+    positions: Vec<Point3>,
+    normals: Vec<Vec3>,
+    uvs: Vec<(f64, f64)>,
+    faces: Vec<MeshFace>,
+    bvh: Bvh,
+    // running sum of the face areas, for picking faces proportional to their area
+    area_cdf: Vec<f64>
+}
+
+#[derive(Clone)]
+pub struct TriangleMesh {
+    data: Arc<MeshData>,
+    material: Box<dyn Material>
+}
+
+impl TriangleMesh {
+    pub fn new(positions: Vec<Point3>, normals: Vec<Vec3>, uvs: Vec<(f64, f64)>, faces: Vec<MeshFace>, material: Box<dyn Material>) -> TriangleMesh {
+        for face in faces.iter() {
+            let is_valid =
+                face.vertices.iter().all(|index| *index < positions.len()) &&
+                face.normals.is_none_or(|indices| indices.iter().all(|index| *index < normals.len())) &&
+                face.uvs.is_none_or(|indices| indices.iter().all(|index| *index < uvs.len()));
+            if !is_valid {
+                panic!("wrong triangle mesh index!");
+            }
+        }
+
+        let normals: Vec<Vec3> = normals.iter().map(|normal| normal.get_normal()).collect();
+        let face_bounds: Vec<Aabb> = faces.iter()
+            .map(|face| Aabb::from_points(&[
+                positions[face.vertices[0]],
+                positions[face.vertices[1]],
+                positions[face.vertices[2]]]))
+            .collect();
+        let bvh = Bvh::new(&face_bounds);
+
+        let mut area_cdf: Vec<f64> = Vec::with_capacity(faces.len());
+        let mut total_area = 0.0;
+        for face in faces.iter() {
+            total_area += get_triangle_area([
+                &positions[face.vertices[0]],
+                &positions[face.vertices[1]],
+                &positions[face.vertices[2]]]);
+            area_cdf.push(total_area);
+        }
+
+        TriangleMesh {
+            data: Arc::new(MeshData { positions, normals, uvs, faces, bvh, area_cdf }),
+            material
+        }
+    }
+
+    pub fn get_triangle_count(&self) -> usize {
+        self.data.faces.len()
+    }
+
+    pub fn get_vertex_count(&self) -> usize {
+        self.data.positions.len()
+    }
+
+    pub fn get_surface_area(&self) -> f64 {
+        match self.data.area_cdf.last() {
+            Some(total_area) => *total_area,
+            None => 0.0
+        }
+    }
+
+    fn get_face_vertices(&self, face_index: usize) -> [&Point3; 3] {
+        let data = &*self.data;
+        let face = &data.faces[face_index];
+        [
+            &data.positions[face.vertices[0]],
+            &data.positions[face.vertices[1]],
+            &data.positions[face.vertices[2]]
+        ]
+    }
+
+    fn hit_face(&self, face_index: usize, ray: &Ray, weight_min: f64, weight_max: f64) -> Result<HitRecord<'_>, ()> {
+        let data = &*self.data;
+        let face = &data.faces[face_index];
+        let vertices = [
+            &data.positions[face.vertices[0]],
+            &data.positions[face.vertices[1]],
+            &data.positions[face.vertices[2]]];
+
+        match intersect_triangle(ray, vertices, weight_min, weight_max) {
+            Some(hit) => {
+                let normals = face.normals.map(|indices| [
+                    &data.normals[indices[0]],
+                    &data.normals[indices[1]],
+                    &data.normals[indices[2]]]);
+                let uvs = match face.uvs {
+                    Some(indices) => [data.uvs[indices[0]], data.uvs[indices[1]], data.uvs[indices[2]]],
+                    None => DEFAULT_TRIANGLE_UVS
+                };
+
+                Ok(make_triangle_record(ray, &hit, vertices, normals, uvs, &*self.material))
+            }
+            None => Err(())
+        }
+    }
+}
+
+impl Hittable for TriangleMesh {
+    fn hit(&self, ray: &Ray, weight_min: f64, weight_max: f64) -> Result<HitRecord<'_>, ()> {
+        self.data.bvh.traverse(ray, weight_min, weight_max, |face_index, ray, weight_min, weight_max| {
+            self.hit_face(face_index, ray, weight_min, weight_max)
+        })
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.data.bvh.get_bounds()
+    }
+
+    fn is_emissive(&self) -> bool {
+        self.material.is_emissive()
+    }
+
+    fn sample_point(&self, origin: &Point3, sampler: &mut dyn Sampler) -> Option<ShapeSample> {
+        let total_area = self.get_surface_area();
+        if total_area <= 0.0 {
+            return None;
+        }
+
+        let target_area = sampler.get_1d() * total_area;
+        let face_index = self.data.area_cdf.partition_point(|area| *area <= target_area).min(self.data.faces.len() - 1);
+
+        let u = sampler.get_2d();
+        let (point, normal) = sample_triangle(self.get_face_vertices(face_index), u.0, u.1);
+        let pdf = area_to_solid_angle_pdf(1.0 / total_area, origin, &point, &normal);
+        if pdf <= 0.0 {
+            return None;
+        }
+
+        Some(ShapeSample { point, normal, pdf })
+    }
+
+    fn pdf_value(&self, origin: &Point3, direction: &Vec3) -> f64 {
+        // remember which face was hit last, that is the closest one once the traversal ends
+        let ray = Ray::new(*origin, *direction);
+        let mut hit_face: Option<(usize, f64)> = None;
+        let result = self.data.bvh.traverse(&ray, 0.0001, f64::MAX, |face_index, ray, weight_min, weight_max| {
+            let hit = intersect_triangle(ray, self.get_face_vertices(face_index), weight_min, weight_max);
+            match hit {
+                Some(hit) => {
+                    hit_face = Some((face_index, hit.weight));
+                    Ok(make_triangle_record(ray, &hit, self.get_face_vertices(face_index), None, DEFAULT_TRIANGLE_UVS, &*self.material))
+                }
+                None => Err(())
+            }
+        });
+
+        match (result, hit_face) {
+            (Ok(_), Some((face_index, weight))) => {
+                let vertices = self.get_face_vertices(face_index);
+                let normal = Vec3::cross(&(*vertices[1] - *vertices[0]), &(*vertices[2] - *vertices[0])).get_normal();
+                let point = ray.get_point(weight);
+                area_to_solid_angle_pdf(1.0 / self.get_surface_area(), origin, &point, &normal)
+            }
+            _ => 0.0
+        }
+    }
+}
@@ -0,0 +1,169 @@
+
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+use crate::output::{OutputError, ImageLayer};
+
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum ExrPixelType {
+    // 16 bit floats, half the size and plenty for color
+    Half,
+    Float
+}
+
+impl ExrPixelType {
+    fn get_id(&self) -> i32 {
+        match self {
+            ExrPixelType::Half => 1,
+            ExrPixelType::Float => 2
+        }
+    }
+
+    fn get_byte_size(&self) -> usize {
+        match self {
+            ExrPixelType::Half => 2,
+            ExrPixelType::Float => 4
+        }
+    }
+}
+
+struct ExrChannel<'a> {
+    name: String,
+    layer: &'a ImageLayer,
+    component: usize
+}
+
+// Uncompressed single part scanline OpenEXR, every layer becomes R, G and B channels prefixed with its name
+// and scalar layers one channel named like the layer.
+pub fn write_exr(path: &Path, size: (usize, usize), layers: &[ImageLayer], pixel_type: ExrPixelType) -> Result<(), OutputError> {
+    let to_output_error = |error: std::io::Error| OutputError::Io { path: path.to_path_buf(), error };
+
+    let mut channels: Vec<ExrChannel> = Vec::new();
+    for layer in layers {
+        if layer.is_scalar {
+            channels.push(ExrChannel { name: layer.name.clone(), layer, component: 0 });
+            continue;
+        }
+
+        for (component, component_name) in ["R", "G", "B"].iter().enumerate() {
+            let name = match layer.name.is_empty() {
+                true => component_name.to_string(),
+                false => format!("{}.{}", layer.name, component_name)
+            };
+            channels.push(ExrChannel { name, layer, component });
+        }
+    }
+    // readers expect the channel list sorted by name
+    channels.sort_by(|lhs, rhs| lhs.name.cmp(&rhs.name));
+
+    let mut header: Vec<u8> = Vec::new();
+    header.extend_from_slice(&[0x76, 0x2f, 0x31, 0x01]);
+    header.extend_from_slice(&2u32.to_le_bytes());
+
+    let mut channel_list: Vec<u8> = Vec::new();
+    for channel in &channels {
+        channel_list.extend_from_slice(channel.name.as_bytes());
+        channel_list.push(0);
+        channel_list.extend_from_slice(&pixel_type.get_id().to_le_bytes());
+        // linear flag and three reserved bytes, then x and y sampling
+        channel_list.extend_from_slice(&[0, 0, 0, 0]);
+        channel_list.extend_from_slice(&1i32.to_le_bytes());
+        channel_list.extend_from_slice(&1i32.to_le_bytes());
+    }
+    channel_list.push(0);
+    write_attribute(&mut header, "channels", "chlist", &channel_list);
+
+    write_attribute(&mut header, "compression", "compression", &[0]);
+
+    let mut window: Vec<u8> = Vec::new();
+    for value in [0, 0, size.0 as i32 - 1, size.1 as i32 - 1] {
+        window.extend_from_slice(&value.to_le_bytes());
+    }
+    write_attribute(&mut header, "dataWindow", "box2i", &window);
+    write_attribute(&mut header, "displayWindow", "box2i", &window);
+
+    // rows from the top of the image
+    write_attribute(&mut header, "lineOrder", "lineOrder", &[0]);
+    write_attribute(&mut header, "pixelAspectRatio", "float", &1.0f32.to_le_bytes());
+    write_attribute(&mut header, "screenWindowCenter", "v2f", &[0; 8]);
+    write_attribute(&mut header, "screenWindowWidth", "float", &1.0f32.to_le_bytes());
+    header.push(0);
+
+    // one chunk per scanline, the offset table points at each of them
+    let line_byte_size = size.0 * channels.len() * pixel_type.get_byte_size();
+    let chunk_byte_size = 8 + line_byte_size;
+    let first_chunk = header.len() + size.1 * 8;
+    for y in 0 .. size.1 {
+        header.extend_from_slice(&((first_chunk + y * chunk_byte_size) as u64).to_le_bytes());
+    }
+
+    let file = File::create(path).map_err(to_output_error)?;
+    let mut writer = BufWriter::new(file);
+    writer.write_all(&header).map_err(to_output_error)?;
+
+    let mut line: Vec<u8> = Vec::with_capacity(chunk_byte_size);
+    for y in 0 .. size.1 {
+        line.clear();
+        line.extend_from_slice(&(y as i32).to_le_bytes());
+        line.extend_from_slice(&(line_byte_size as i32).to_le_bytes());
+        for channel in &channels {
+            for x in 0 .. size.0 {
+                let value = channel.layer.pixels[y * size.0 + x][channel.component] as f32;
+                match pixel_type {
+                    ExrPixelType::Half => line.extend_from_slice(&f32_to_half(value).to_le_bytes()),
+                    ExrPixelType::Float => line.extend_from_slice(&value.to_le_bytes())
+                }
+            }
+        }
+        writer.write_all(&line).map_err(to_output_error)?;
+    }
+
+    writer.flush().map_err(to_output_error)
+}
+
+fn write_attribute(header: &mut Vec<u8>, name: &str, attribute_type: &str, value: &[u8]) {
+    header.extend_from_slice(name.as_bytes());
+    header.push(0);
+    header.extend_from_slice(attribute_type.as_bytes());
+    header.push(0);
+    header.extend_from_slice(&(value.len() as i32).to_le_bytes());
+    header.extend_from_slice(value);
+}
+
+// IEEE 754 binary16 with round to nearest even, too large values become infinity.
+pub fn f32_to_half(value: f32) -> u16 {
+    let bits = value.to_bits();
+    let sign = ((bits >> 16) & 0x8000) as u16;
+    let exponent = ((bits >> 23) & 0xff) as i32;
+    let mantissa = bits & 0x7f_ffff;
+
+    // infinity stays infinity, nan keeps a mantissa bit set
+    if exponent == 0xff {
+        return sign | 0x7c00 | if mantissa != 0 { 0x200 } else { 0 };
+    }
+
+    let half_exponent = exponent - 127 + 15;
+    if half_exponent >= 0x1f {
+        return sign | 0x7c00;
+    }
+
+    // below the smallest normal half the implicit leading bit has to be shifted in as well
+    let (mantissa, shift, half) = match half_exponent <= 0 {
+        true => {
+            if half_exponent < -10 {
+                return sign;
+            }
+            let mantissa = mantissa | 0x80_0000;
+            let shift = (14 - half_exponent) as u32;
+            (mantissa, shift, mantissa >> shift)
+        }
+        false => (mantissa, 13, ((half_exponent as u32) << 10) | (mantissa >> 13))
+    };
+
+    // a carry out of the mantissa correctly moves on to the next exponent
+    let round_bit = 1 << (shift - 1);
+    let is_rounded_up = mantissa & round_bit != 0 && mantissa & (4 * round_bit - 1) != round_bit;
+    sign | (half + is_rounded_up as u32) as u16
+}
@@ -0,0 +1,47 @@
+
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+use crate::math::vec3::Color;
+use crate::output::OutputError;
+
+
+// Radiance RGBE picture with flat (not run length encoded) scanlines from the top.
+pub fn write_hdr(path: &Path, size: (usize, usize), pixels: &[Color]) -> Result<(), OutputError> {
+    let to_output_error = |error: std::io::Error| OutputError::Io { path: path.to_path_buf(), error };
+
+    let file = File::create(path).map_err(to_output_error)?;
+    let mut writer = BufWriter::new(file);
+
+    write!(writer, "#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n\n-Y {} +X {}\n", size.1, size.0).map_err(to_output_error)?;
+    let data: Vec<u8> = pixels.iter().flat_map(to_rgbe).collect();
+    writer.write_all(&data).map_err(to_output_error)?;
+    writer.flush().map_err(to_output_error)
+}
+
+// Three 8 bit mantissas sharing the exponent of the brightest channel.
+fn to_rgbe(color: &Color) -> [u8; 4] {
+    let red = color[0].max(0.0);
+    let green = color[1].max(0.0);
+    let blue = color[2].max(0.0);
+    let brightest = red.max(green).max(blue);
+    if brightest.is_nan() || brightest < 1e-32 {
+        return [0; 4];
+    }
+
+    // brightest = fraction * 2^exponent with fraction in [0.5, 1)
+    let mut exponent = brightest.log2().floor() as i32 + 1;
+    if brightest / 2.0_f64.powi(exponent) >= 1.0 {
+        exponent += 1;
+    }
+    let exponent = exponent.clamp(-128, 127);
+
+    let scale = 256.0 / 2.0_f64.powi(exponent);
+    [
+        (red * scale).min(255.0) as u8,
+        (green * scale).min(255.0) as u8,
+        (blue * scale).min(255.0) as u8,
+        (exponent + 128) as u8
+    ]
+}
@@ -0,0 +1,122 @@
+
+pub mod ppm;
+pub mod exr;
+pub mod hdr;
+
+#[cfg(test)]
+mod tests;
+
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use crate::math::vec3::Color;
+use crate::raytracer::RayTracerBuffer;
+use crate::output::exr::{ExrPixelType, write_exr};
+use crate::output::hdr::write_hdr;
+
+
+// Linear values for every pixel, rows from the top of the image.
+pub struct ImageLayer {
+    // empty for the main color layer
+    pub name: String,
+    pub pixels: Vec<Color>,
+    // only the first channel means something, like depth or ids
+    pub is_scalar: bool
+}
+
+impl ImageLayer {
+    pub fn new(name: &str, pixels: Vec<Color>) -> ImageLayer {
+        ImageLayer {
+            name: name.to_string(),
+            pixels,
+            is_scalar: false
+        }
+    }
+
+    pub fn new_scalar(name: &str, pixels: Vec<Color>) -> ImageLayer {
+        ImageLayer {
+            name: name.to_string(),
+            pixels,
+            is_scalar: true
+        }
+    }
+}
+
+pub enum OutputError {
+    UnsupportedFormat(PathBuf),
+    Io {
+        path: PathBuf,
+        error: std::io::Error
+    },
+    Encode {
+        path: PathBuf,
+        message: String
+    }
+}
+
+impl fmt::Display for OutputError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OutputError::UnsupportedFormat(path) => {
+                write!(f, "{path}: unsupported image format, use .png, .ppm, .exr or .hdr", path = path.display())
+            }
+            OutputError::Io { path, error } => {
+                write!(f, "{path}: {error}", path = path.display(), error = error)
+            }
+            OutputError::Encode { path, message } => {
+                write!(f, "{path}: {message}", path = path.display(), message = message)
+            }
+        }
+    }
+}
+
+impl fmt::Debug for OutputError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(self, f)
+    }
+}
+
+pub fn is_supported_format(path: &Path) -> bool {
+    matches!(get_extension(path).as_deref(), Some("ppm") | Some("png") | Some("exr") | Some("hdr"))
+}
+
+// formats that hold every layer in one file instead of only the first
+pub fn is_layered_format(path: &Path) -> bool {
+    matches!(get_extension(path).as_deref(), Some("exr"))
+}
+
+// Picks the encoder from the file extension.
+// 8-bit formats take the display encoded buffer, floating point formats the unclamped layers.
+// Radiance pictures only hold the first layer.
+pub fn write_image(path: &Path, buffer: &RayTracerBuffer, layers: &[ImageLayer], exr_pixel_type: ExrPixelType) -> Result<(), OutputError> {
+    let size = buffer.get_buffer_size();
+    match get_extension(path).as_deref() {
+        Some("ppm") => ppm::write_ppm(path, buffer),
+        Some("png") => write_png(path, buffer),
+        Some("exr") => write_exr(path, size, layers, exr_pixel_type),
+        Some("hdr") => write_hdr(path, size, &layers[0].pixels),
+        _ => Err(OutputError::UnsupportedFormat(path.to_path_buf()))
+    }
+}
+
+fn get_extension(path: &Path) -> Option<String> {
+    path.extension()
+        .and_then(|extension| extension.to_str())
+        .map(|extension| extension.to_ascii_lowercase())
+}
+
+fn write_png(path: &Path, buffer: &RayTracerBuffer) -> Result<(), OutputError> {
+    let size = buffer.get_buffer_size();
+    let result = image::save_buffer_with_format(
+        path,
+        buffer.get_buffer(),
+        size.0 as u32,
+        size.1 as u32,
+        image::ColorType::Rgb8,
+        image::ImageFormat::Png);
+
+    result.map_err(|error| match error {
+        image::ImageError::IoError(error) => OutputError::Io { path: path.to_path_buf(), error },
+        error => OutputError::Encode { path: path.to_path_buf(), message: error.to_string() }
+    })
+}
@@ -0,0 +1,21 @@
+
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+use crate::output::OutputError;
+use crate::raytracer::RayTracerBuffer;
+
+
+// Binary (P6) portable pixmap, rows top to bottom.
+pub fn write_ppm(path: &Path, buffer: &RayTracerBuffer) -> Result<(), OutputError> {
+    let to_output_error = |error: std::io::Error| OutputError::Io { path: path.to_path_buf(), error };
+
+    let file = File::create(path).map_err(to_output_error)?;
+    let mut writer = BufWriter::new(file);
+
+    let size = buffer.get_buffer_size();
+    write!(writer, "P6\n{} {}\n255\n", size.0, size.1).map_err(to_output_error)?;
+    writer.write_all(buffer.get_buffer()).map_err(to_output_error)?;
+    writer.flush().map_err(to_output_error)
+}
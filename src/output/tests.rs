@@ -0,0 +1,40 @@
+
+// Half float conversion for the OpenEXR writer, checked against bit patterns of IEEE 754 binary16.
+
+use crate::output::exr::f32_to_half;
+
+
+#[test]
+fn exact_values_keep_their_bits() {
+    assert_eq!(f32_to_half(0.0), 0x0000);
+    assert_eq!(f32_to_half(-0.0), 0x8000);
+    assert_eq!(f32_to_half(1.0), 0x3c00);
+    assert_eq!(f32_to_half(-2.0), 0xc000);
+    assert_eq!(f32_to_half(0.5), 0x3800);
+    assert_eq!(f32_to_half(65504.0), 0x7bff);
+    // smallest normal and smallest subnormal half
+    assert_eq!(f32_to_half(2.0_f32.powi(-14)), 0x0400);
+    assert_eq!(f32_to_half(2.0_f32.powi(-24)), 0x0001);
+}
+
+#[test]
+fn rounding_is_to_nearest_even() {
+    assert_eq!(f32_to_half(0.1), 0x2e66);
+    // halfway between two halves goes to the even one
+    assert_eq!(f32_to_half(1.0 + 2.0_f32.powi(-11)), 0x3c00);
+    assert_eq!(f32_to_half(1.0 + 3.0 * 2.0_f32.powi(-11)), 0x3c02);
+    // a carry out of the mantissa moves on to the next exponent
+    assert_eq!(f32_to_half(2.0 - 2.0_f32.powi(-12)), 0x4000);
+    assert_eq!(f32_to_half(2.0_f32.powi(-25)), 0x0000);
+    assert_eq!(f32_to_half(1.5 * 2.0_f32.powi(-25)), 0x0001);
+    assert_eq!(f32_to_half(2.0_f32.powi(-26)), 0x0000);
+}
+
+#[test]
+fn out_of_range_values_become_infinity() {
+    assert_eq!(f32_to_half(65520.0), 0x7c00);
+    assert_eq!(f32_to_half(-1e10), 0xfc00);
+    assert_eq!(f32_to_half(f32::INFINITY), 0x7c00);
+    assert_eq!(f32_to_half(f32::NAN) & 0x7c00, 0x7c00);
+    assert_ne!(f32_to_half(f32::NAN) & 0x03ff, 0);
+}
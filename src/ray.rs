@@ -1,9 +1,13 @@
 
 use crate::math::vec3::{Point3, Vec3};
 
+// cone_width and cone_spread describe the ray cone used to pick texture detail,
+// a ray without one has both at zero and always reads the finest detail.
 pub struct Ray {
     origin: Point3,
-    direction: Vec3
+    direction: Vec3,
+    cone_width: f64,
+    cone_spread: f64
 }
 
 impl Ray {
@@ -12,7 +16,25 @@ impl Ray {
     }
 
     pub fn new(origin: Point3, direction: Vec3) -> Ray {
-        Ray { origin, direction }
+        Ray::new_with_cone(origin, direction, 0.0, 0.0)
+    }
+
+    pub fn new_with_cone(origin: Point3, direction: Vec3, cone_width: f64, cone_spread: f64) -> Ray {
+        Ray { origin, direction, cone_width, cone_spread }
+    }
+
+    pub fn set_cone(&mut self, cone_width: f64, cone_spread: f64) {
+        self.cone_width = cone_width;
+        self.cone_spread = cone_spread;
+    }
+
+    // width of the cone where the ray reaches weight
+    pub fn get_cone_width(&self, weight: f64) -> f64 {
+        self.cone_width + self.cone_spread * weight * self.direction.length()
+    }
+
+    pub fn get_cone_spread(&self) -> f64 {
+        self.cone_spread
     }
 
     pub fn get_origin(&self) -> &Point3 {
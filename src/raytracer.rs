@@ -1,6 +1,7 @@
 
-use std::sync::mpsc::channel;
-use std::sync::mpsc::{Sender, Receiver};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
 
 use crate::UserEvent;
 use crate::material::lambertian::Lambertian;
@@ -8,26 +9,76 @@ use crate::material::metal::Metal;
 use crate::material::dielectric::Dielectric;
 use crate::math::vec3::{Color, Point3};
 use crate::threading::RayWorkerManager;
-use crate::threading::ray_worker::{RayWorkerSettings, RayResult};
+use crate::threading::ray_worker::RayWorkerSettings;
+use crate::threading::tile::{Tile, TileQueue, TileOrder, make_tiles};
+use crate::threading::render_control::RenderControl;
 use crate::world::World;
 use crate::object::sphere::Sphere;
-use crate::camera::{Camera, CameraSettings};
-
-use rand::{thread_rng, Rng};
+use crate::camera::Camera;
+use crate::film::{Film, Aov};
+use crate::display::DisplayTransform;
+use crate::denoise::{DenoiseMode, denoise_film};
+use crate::sampler::SamplerType;
+use crate::scene::{Scene, SceneCamera};
+
+use rand::{Rng, SeedableRng};
+use rand::rngs::StdRng;
 use speedy2d::window::UserEventSender;
 
 
+// the denoiser takes a while on large images, progressive renders only run it this often
+const PROGRESSIVE_DENOISE_INTERVAL: Duration = Duration::from_secs(2);
+
+
 #[derive(Clone, Copy)]
 pub enum RayTracerState {
     Idle,
-    Working
+    Working,
+    // workers are waiting between two tiles until resume is called
+    Paused,
+    // stopped before every sample was taken, the buffer keeps the partial image
+    Cancelled,
+    // a worker panicked, the render was stopped and its image is incomplete
+    Failed
 }
 
 #[derive(Clone, Copy)]
 pub struct RayTracerSettings {
-    sample_count: u32,
-    bound_limit: u32,
-    receive_limit: u32
+    pub sample_count: u32,
+    pub bound_limit: u32,
+    // display updates per second while rendering
+    pub display_rate: u32,
+    pub tile_size: usize,
+    pub tile_order: TileOrder,
+    pub display: DisplayTransform,
+    pub denoise: DenoiseMode,
+    pub denoise_iterations: u32,
+    // relative error below which a pixel stops taking samples, zero samples every pixel sample_count times
+    pub adaptive_threshold: f64,
+    // samples every pixel gets before it may stop
+    pub min_sample_count: u32,
+    pub sampler: SamplerType,
+    // the same seed, scene and sample count always give the same image
+    pub seed: u64
+}
+
+impl RayTracerSettings {
+    pub fn new_default() -> RayTracerSettings {
+        RayTracerSettings {
+            sample_count: 1000,
+            bound_limit: 100,
+            display_rate: 30,
+            tile_size: 32,
+            tile_order: TileOrder::Spiral,
+            display: DisplayTransform::new_default(),
+            denoise: DenoiseMode::Off,
+            denoise_iterations: 5,
+            adaptive_threshold: 0.0,
+            min_sample_count: 16,
+            sampler: SamplerType::Sobol,
+            seed: 0
+        }
+    }
 }
 
 #[derive(Clone)]
@@ -38,16 +89,17 @@ pub struct RayTracerBuffer {
 }
 
 impl RayTracerBuffer {
-    pub fn set_buffer(&mut self, pos: (usize, usize), color: &Color, flip_y: bool) {
+    // rgb is already display encoded, see DisplayTransform
+    pub fn set_buffer(&mut self, pos: (usize, usize), rgb: [u8; 3], flip_y: bool) {
         let cur_y = match flip_y {
             true => { self.buffer_size.1 - pos.1 - 1 }
             false => { pos.1 }
         };
 
-        let index: usize = (self.buffer_size.0 * cur_y * 3 + pos.0 * 3) as usize;
-        self.buffer[index + 0] = (color[0] * 256.0) as u8;
-        self.buffer[index + 1] = (color[1] * 256.0) as u8;
-        self.buffer[index + 2] = (color[2] * 256.0) as u8;
+        let index = (self.buffer_size.0 * cur_y + pos.0) * 3;
+        self.buffer[index] = rgb[0];
+        self.buffer[index + 1] = rgb[1];
+        self.buffer[index + 2] = rgb[2];
     }
 
     pub fn resize(&mut self, new_size: (usize, usize)) {
@@ -77,28 +129,41 @@ impl RayTracerBuffer {
 
 pub struct RayTracer {
     // systems
-    event_sender: UserEventSender<UserEvent>,
+    event_sender: Option<UserEventSender<UserEvent>>,
     ray_worker_manager: RayWorkerManager,
-    buffer_sender: Sender<RayResult>,
-    buffer_receiver: Receiver<RayResult>,
     core_thread_nums: usize,
+    worker_nums_override: Option<usize>,
+    control: Arc<RenderControl>,
 
     // raw datas
     buffer: RayTracerBuffer,
+    film: Arc<Film>,
+    // pass shown in the buffer
+    aov: Aov,
+    // beauty after the last denoise, rows from the top
+    denoised: Option<Vec<Color>>,
+    show_denoised: bool,
 
     // scene
-    world: World,
+    // read by every worker at once, never cloned
+    world: Arc<World>,
     camera: Camera,
+    scene_camera: SceneCamera,
     settings: RayTracerSettings,
+    is_scene_loaded: bool,
 
     // state
     state: RayTracerState,
-    received_packet: usize,
+    expected_packet: usize,
+    last_display_update: Instant,
+    last_denoise: Instant,
+    printed_percentage: u32,
     buffer_updated: bool
 }
 
 impl RayTracer {
-    pub fn new(init_size: (usize, usize), core_thread_nums: usize, event_sender: UserEventSender<UserEvent>) -> RayTracer {
+    // Without an event sender (headless) progress is printed to stdout instead of the window title.
+    pub fn new(init_size: (usize, usize), core_thread_nums: usize, event_sender: Option<UserEventSender<UserEvent>>) -> RayTracer {
         let mut new_buffer: Vec<u8> = Vec::new();
         let new_byte_size = init_size.0 * init_size.1 * 3;
         new_buffer.resize(new_byte_size, 0);
@@ -109,26 +174,27 @@ impl RayTracer {
             buffer_byte_size: new_byte_size
         };
 
-        let ray_tracer_settings = RayTracerSettings {
-            sample_count: 1000,
-            bound_limit: 100,
-            receive_limit: 10
-        };
-
-        let (sender, receiver): (Sender<RayResult>, Receiver<RayResult>) = channel();
-
         RayTracer {
             event_sender,
             ray_worker_manager: RayWorkerManager::new(),
-            buffer_sender: sender,
-            buffer_receiver: receiver,
             core_thread_nums,
+            worker_nums_override: None,
+            control: Arc::new(RenderControl::new()),
             buffer: raytracer_buffer,
-            world: World::new_default(),
+            film: Arc::new(Film::new(init_size)),
+            aov: Aov::Beauty,
+            denoised: None,
+            show_denoised: false,
+            world: Arc::new(World::new_default()),
             camera: Camera::new_default(),
-            settings: ray_tracer_settings,
+            scene_camera: SceneCamera::new_default(),
+            settings: RayTracerSettings::new_default(),
+            is_scene_loaded: false,
             state: RayTracerState::Idle,
-            received_packet: 0,
+            expected_packet: 0,
+            last_display_update: Instant::now(),
+            last_denoise: Instant::now(),
+            printed_percentage: 0,
             buffer_updated: false
         }
     }
@@ -137,39 +203,75 @@ impl RayTracer {
         self.buffer.resize(new_size);
     } 
 
+    pub fn load_scene(&mut self, scene: Scene) {
+        let mut world = scene.world;
+        world.build_bvh();
+        world.build_light_list();
+        self.world = Arc::new(world);
+        self.scene_camera = scene.camera;
+        self.settings = scene.settings;
+        self.is_scene_loaded = true;
+    }
+
+    pub fn get_settings(&self) -> RayTracerSettings {
+        self.settings
+    }
+
+    pub fn set_settings(&mut self, settings: RayTracerSettings) {
+        self.settings = settings;
+    }
+
+    // Uses exactly worker_nums threads instead of all cores but core_thread_nums.
+    pub fn set_worker_nums(&mut self, worker_nums: Option<usize>) {
+        self.worker_nums_override = worker_nums;
+    }
+
     pub fn run(&mut self) {
-        if let RayTracerState::Idle = self.state {} else {
-            return;
+        match self.state {
+            RayTracerState::Idle | RayTracerState::Cancelled | RayTracerState::Failed => { }
+            _ => return
         }
 
         self.state = RayTracerState::Working;
 
-        self.build_world();
+        if !self.is_scene_loaded {
+            self.build_world();
+        }
         self.update_camera();
 
-        let mut ray_worker_settings = RayWorkerSettings {
-            screen_size: self.get_buffer_size(),
-            bound_y: (0, 0),
-            sample_count: self.settings.sample_count,
+        let screen_size = self.get_buffer_size();
+        self.film = Arc::new(Film::new(screen_size));
+        self.last_display_update = Instant::now();
+        self.denoised = None;
+        self.show_denoised = self.settings.denoise != DenoiseMode::Off;
+        self.last_denoise = Instant::now();
+
+        let ray_worker_settings = RayWorkerSettings {
+            screen_size,
             bound_limit: self.settings.bound_limit,
+            adaptive_threshold: self.settings.adaptive_threshold,
+            min_sample_count: self.settings.min_sample_count,
+            sampler: self.settings.sampler,
+            sample_count: self.settings.sample_count,
+            seed: self.settings.seed
         };
 
-        let cpu_nums = num_cpus::get();
-        let worker_nums = if cpu_nums > self.core_thread_nums { cpu_nums - self.core_thread_nums } else { 1 };
-        let per_worker_y = self.get_buffer_size().1 / worker_nums;
-        let mut prev_max_bound: usize = 0;
-        for i in 0 .. worker_nums {
-            ray_worker_settings.bound_y.0 = prev_max_bound;
-            ray_worker_settings.bound_y.1 += per_worker_y;
-            prev_max_bound = ray_worker_settings.bound_y.1;
-            if i == (worker_nums - 1) {
-                ray_worker_settings.bound_y.1 = self.get_buffer_size().1;
-            }
+        // every render gets its own signals so a cancel can never leak into the next one
+        self.control = Arc::new(RenderControl::new());
+
+        let tiles = make_tiles(screen_size, self.settings.tile_size, self.settings.tile_order);
+        let tile_queue = Arc::new(TileQueue::new(tiles, self.settings.sample_count));
+        self.expected_packet = tile_queue.get_job_count();
 
-            let copied_world = self.world.clone();
+        let cpu_nums = num_cpus::get();
+        let worker_nums = match self.worker_nums_override {
+            Some(worker_nums) => worker_nums.max(1),
+            None => if cpu_nums > self.core_thread_nums { cpu_nums - self.core_thread_nums } else { 1 }
+        };
+        for _ in 0 .. worker_nums {
+            let shared_world = Arc::clone(&self.world);
             let copied_camera = self.camera.clone();
-            let copied_sender = self.buffer_sender.clone();
-            self.ray_worker_manager.start_worker(copied_world, copied_camera, copied_sender, ray_worker_settings);
+            self.ray_worker_manager.start_worker(shared_world, copied_camera, tile_queue.clone(), Arc::clone(&self.control), Arc::clone(&self.film), ray_worker_settings);
         }
     }
 
@@ -178,35 +280,145 @@ impl RayTracer {
             return;
         }
 
-        let worker_nums = self.ray_worker_manager.get_worker_nums();
-        let expected_packet = worker_nums * self.settings.sample_count as usize;
-        if self.received_packet >= expected_packet {
-            self.print_message("finished raytracing!", false);
+        // sleep until the next display update is due or the last tile is in
+        let frame_time = Duration::from_secs_f64(1.0 / self.settings.display_rate.max(1) as f64);
+        while self.film.get_finished_jobs() < self.expected_packet && !self.control.is_failed() {
+            let elapsed = self.last_display_update.elapsed();
+            if elapsed >= frame_time {
+                break;
+            }
+            thread::sleep((frame_time - elapsed).min(Duration::from_millis(5)));
+        }
+
+        if self.control.is_failed() {
             self.ray_worker_manager.join_workers();
-            self.state = RayTracerState::Idle;
-            self.received_packet = 0;
+            self.print_message("a render thread failed, the render was stopped", false);
+            self.state = RayTracerState::Failed;
+            self.printed_percentage = 0;
             return;
         }
 
-        let mut receive_count = 0;
-        while receive_count < self.settings.receive_limit {
-            match self.buffer_receiver.recv() {
-                Ok(ray_result) => {
-                    self.received_packet += 1;
-                    self.buffer_updated = true;
-                    self.apply_worker_buffer(ray_result);
-                }
-                Err(_) => { 
-                    break;
-                }
+        self.last_display_update = Instant::now();
+        self.resolve_film();
+
+        if self.film.get_finished_jobs() >= self.expected_packet {
+            if self.settings.denoise != DenoiseMode::Off {
+                self.denoise();
+            }
+            self.print_message("finished raytracing!", false);
+            if self.settings.adaptive_threshold > 0.0 {
+                println!("{samples:.1} samples per pixel on average", samples = self.film.get_average_sample_count());
             }
+            self.ray_worker_manager.join_workers();
+            self.state = RayTracerState::Idle;
+            self.printed_percentage = 0;
+            return;
+        }
 
-            receive_count += 1;
+        if self.settings.denoise == DenoiseMode::Progressive && self.last_denoise.elapsed() >= PROGRESSIVE_DENOISE_INTERVAL {
+            self.denoise();
         }
 
-        let progress_percentage = (self.received_packet as f64 / expected_packet as f64) * 100.0;
+        let progress_percentage = self.get_progress_percentage();
         let message = format!("progress: {percentage:.2}%", percentage = progress_percentage);
         self.print_message(&message, true);
+
+        // headless runs have no title bar, report every whole percent on stdout
+        let whole_percentage = progress_percentage as u32;
+        if self.event_sender.is_none() && whole_percentage > self.printed_percentage {
+            self.printed_percentage = whole_percentage;
+            println!("{msg}", msg = message);
+        }
+    }
+
+    pub fn pause(&mut self) {
+        if let RayTracerState::Working = self.state {
+            self.control.set_paused(true);
+            self.state = RayTracerState::Paused;
+            self.resolve_film();
+            if self.show_denoised {
+                self.denoise();
+            }
+            self.print_message(&format!("paused at {percentage:.2}%", percentage = self.get_progress_percentage()), false);
+        }
+    }
+
+    pub fn resume(&mut self) {
+        if let RayTracerState::Paused = self.state {
+            self.control.set_paused(false);
+            self.state = RayTracerState::Working;
+            self.print_message("resumed raytracing", false);
+        }
+    }
+
+    // Stops the workers after the tile they are on, blocks until all of them have exited.
+    pub fn cancel(&mut self) {
+        match self.state {
+            RayTracerState::Working | RayTracerState::Paused => { }
+            _ => return
+        }
+
+        self.control.cancel();
+        self.ray_worker_manager.join_workers();
+
+        // tiles finished before the workers saw the signal still belong in the image
+        self.resolve_film();
+        if self.show_denoised {
+            self.denoise();
+        }
+
+        self.print_message(&format!("cancelled at {percentage:.2}%", percentage = self.get_progress_percentage()), false);
+        self.state = RayTracerState::Cancelled;
+        self.printed_percentage = 0;
+    }
+
+    // Shows the film with another display transform, nothing is rendered again.
+    pub fn set_display_transform(&mut self, display: DisplayTransform) {
+        self.settings.display = display;
+        self.refresh_buffer();
+    }
+
+    // Filters the film as it is now, the result is shown instead of the beauty while show_denoised is set.
+    pub fn denoise(&mut self) {
+        self.denoised = Some(denoise_film(&self.film, self.settings.denoise_iterations));
+        self.last_denoise = Instant::now();
+        if self.show_denoised {
+            self.refresh_buffer();
+        }
+    }
+
+    // Switches between the raw and the denoised beauty, denoises first when there is nothing to show yet.
+    pub fn set_show_denoised(&mut self, show_denoised: bool) {
+        self.show_denoised = show_denoised;
+        match (show_denoised, &self.denoised) {
+            (true, None) => self.denoise(),
+            _ => self.refresh_buffer()
+        }
+    }
+
+    pub fn get_show_denoised(&self) -> bool {
+        self.show_denoised
+    }
+
+    pub fn get_denoised_image(&self) -> Option<&Vec<Color>> {
+        self.denoised.as_ref()
+    }
+
+    // Shows another pass of the film, nothing is rendered again.
+    pub fn set_aov(&mut self, aov: Aov) {
+        self.aov = aov;
+        self.refresh_buffer();
+    }
+
+    pub fn get_aov(&self) -> Aov {
+        self.aov
+    }
+
+    pub fn get_progress_percentage(&self) -> f64 {
+        if self.expected_packet == 0 {
+            return 0.0;
+        }
+        (self.film.get_finished_jobs() as f64 / self.expected_packet as f64) * 100.0
     }
 
     pub fn get_raytracer_state(&self) -> RayTracerState {
@@ -222,6 +434,10 @@ impl RayTracer {
         &self.buffer
     }
 
+    pub fn get_film(&self) -> Arc<Film> {
+        Arc::clone(&self.film)
+    }
+
     pub fn get_buffer_size(&self) -> (usize, usize) {
         self.buffer.get_buffer_size()
     }
@@ -231,34 +447,76 @@ impl RayTracer {
             println!("{msg}", msg = message);
         }
 
-        let event = UserEvent::SetHeader(message.to_string());
-        let result = self.event_sender.send_event(event);
-        match result {
-            _ => {}
+        if let Some(event_sender) = &self.event_sender {
+            let event = UserEvent::SetHeader(message.to_string());
+            // the window may already be closed, the message is dropped then
+            let _ = event_sender.send_event(event);
         }
     }
 
-    fn apply_worker_buffer(&mut self, ray_result: RayResult) {
-        for x in 0 .. self.get_buffer_size().0 {
-            for y in ray_result.bound_y.0 .. ray_result.bound_y.1 {
-                let offseted_y = y - ray_result.bound_y.0;
-                let target_index = self.get_buffer_size().0 * offseted_y + x;
-                let target_color = ray_result.srgb_buffer[target_index];
-                self.buffer.set_buffer((x, y), &target_color, true);
+    // Converts the tiles that changed since the last call into the display buffer.
+    // A denoised image stays on screen until the next denoise replaces it.
+    fn resolve_film(&mut self) {
+        let dirty_tiles = self.film.take_dirty_tiles();
+        if self.is_showing_denoised() {
+            return;
+        }
+
+        for tile in dirty_tiles {
+            self.resolve_tile(&tile);
+        }
+    }
+
+    // Converts the whole image again, after anything but the film changed.
+    fn refresh_buffer(&mut self) {
+        if !self.is_showing_denoised() {
+            let size = self.film.get_size();
+            self.resolve_tile(&Tile { x: 0, y: 0, width: size.0, height: size.1 });
+            return;
+        }
+
+        let size = self.film.get_size();
+        if let Some(denoised) = &self.denoised {
+            for (index, color) in denoised.iter().enumerate() {
+                // rows from the top, the dither still uses film positions
+                let position = (index % size.0, index / size.0);
+                let rgb = self.settings.display.encode(color, (position.0, size.1 - position.1 - 1));
+                self.buffer.set_buffer(position, rgb, false);
             }
         }
+        self.buffer_updated = true;
+    }
+
+    fn is_showing_denoised(&self) -> bool {
+        self.show_denoised && self.denoised.is_some() && self.aov == Aov::Beauty
+    }
+
+    fn resolve_tile(&mut self, tile: &Tile) {
+        let averages = self.film.get_tile_average(tile, self.aov);
+        let range = match self.aov {
+            Aov::SampleCount => self.film.get_max_sample_count() as f64,
+            _ => self.film.get_max_depth()
+        };
+        for (index, average) in averages.iter().enumerate() {
+            let position = (tile.x + index % tile.width, tile.y + index / tile.width);
+            let rgb = self.settings.display.encode_aov(self.aov, average, range, position);
+            self.buffer.set_buffer(position, rgb, true);
+        }
+        self.buffer_updated = true;
     }
 
     fn build_world(&mut self) {
+        let mut world = World::new_default();
+
         // ground
         let ground_material = Lambertian::new(Color::new(0.5, 0.5, 0.5));
         let ground_mesh = Sphere::new(Point3::new(0.0, -1000.0, 0.0), 1000.0, Box::new(ground_material));
-        self.world.add_object(Box::new(ground_mesh));
+        world.add_object(Box::new(ground_mesh));
     
-        // random small spheres
+        // random small spheres, placed by the render seed
+        let mut rng = StdRng::seed_from_u64(self.settings.seed);
         for a in -15 .. 15 {
             for b in -15 .. 15 {
-                let mut rng = thread_rng();
                 let center = Point3::new(
                     a as f64 + 0.9 * rng.gen_range(0.0 .. 1.0), 
                     0.2,
@@ -269,11 +527,11 @@ impl RayTracer {
                 if can_spawn {
                     let choose_material = rng.gen_range(0.0 .. 1.0);
                     let mesh = if choose_material < 0.5 { // diffuse
-                        let albedo = Color::rand() * Color::rand();
+                        let albedo = Color::rand(&mut rng) * Color::rand(&mut rng);
                         let material = Lambertian::new(albedo);
                         Sphere::new(center, 0.2, Box::new(material))
                     } else if choose_material < 0.8 { // metal
-                        let albedo = Color::rand_range((0.5, 1.0));
+                        let albedo = Color::rand_range(&mut rng, (0.5, 1.0));
                         let fuzziness = rng.gen_range(0.0 .. 0.1);
                         let material = Metal::new(albedo, fuzziness);
                         Sphere::new(center, 0.2, Box::new(material))
@@ -282,36 +540,34 @@ impl RayTracer {
                         Sphere::new(center, 0.2, Box::new(material))
                     };
 
-                    self.world.add_object(Box::new(mesh));
+                    world.add_object(Box::new(mesh));
                 };
             }
+        }
 
-            // big sphere
-            let center_material = Dielectric::new(1.5);
-            let center_mesh = Sphere::new(Point3::new(0.0, 1.0, 0.0), 1.0, Box::new(center_material));
-            self.world.add_object(Box::new(center_mesh));
+        // big sphere
+        let center_material = Dielectric::new(1.5);
+        let center_mesh = Sphere::new(Point3::new(0.0, 1.0, 0.0), 1.0, Box::new(center_material));
+        world.add_object(Box::new(center_mesh));
 
-            let back_material = Lambertian::new(Color::new(0.4, 0.2, 0.1));
-            let back_mesh = Sphere::new(Point3::new(-4.0, 1.0, 0.0), 1.0, Box::new(back_material));
-            self.world.add_object(Box::new(back_mesh));
+        let back_material = Lambertian::new(Color::new(0.4, 0.2, 0.1));
+        let back_mesh = Sphere::new(Point3::new(-4.0, 1.0, 0.0), 1.0, Box::new(back_material));
+        world.add_object(Box::new(back_mesh));
 
-            let front_material = Metal::new(Color::new(0.7, 0.6, 0.5), 0.0);
-            let front_mesh = Sphere::new(Point3::new(4.0, 1.0, 0.0), 1.0, Box::new(front_material));
-            self.world.add_object(Box::new(front_mesh));
-        }
+        let front_material = Metal::new(Color::new(0.7, 0.6, 0.5), 0.0);
+        let front_mesh = Sphere::new(Point3::new(4.0, 1.0, 0.0), 1.0, Box::new(front_material));
+        world.add_object(Box::new(front_mesh));
+
+        world.build_bvh();
+        world.build_light_list();
+        self.world = Arc::new(world);
     }
 
     fn update_camera(&mut self) {
-        let look_from = Point3::new(13.0, 2.0, 3.0);
-        let look_to = Point3::new(0.0, 0.0, 0.0);
         let aspect_ratio = self.get_buffer_size().0 as f64 / self.get_buffer_size().1 as f64;
-
-        let field_of_view = 20.0;
-        let aperture = 0.1;
-        let dist_to_focus = 10.0;
-        let settings = CameraSettings::new(field_of_view, aperture, dist_to_focus);
-
-        self.camera.update(look_from, look_to, aspect_ratio, settings);
+        let scene_camera = self.scene_camera;
+        self.camera.update(scene_camera.look_from, scene_camera.look_to, aspect_ratio, scene_camera.settings);
+        self.camera.set_image_height(self.get_buffer_size().1);
     }
 
 }
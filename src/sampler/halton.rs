@@ -0,0 +1,74 @@
+
+use crate::math::hash::{hash_values, to_unit_float};
+use crate::sampler::{Sampler, hash_pixel, permute};
+
+
+// dimensions past the last prime fall back to hashed random numbers
+const PRIMES: [u32; 64] = [
+    2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37, 41, 43, 47, 53,
+    59, 61, 67, 71, 73, 79, 83, 89, 97, 101, 103, 107, 109, 113, 127, 131,
+    137, 139, 149, 151, 157, 163, 167, 173, 179, 181, 191, 193, 197, 199, 211, 223,
+    227, 229, 233, 239, 241, 251, 257, 263, 269, 271, 277, 281, 283, 293, 307, 311
+];
+
+// The radical inverse of the sample index in one prime base per dimension.
+// Plain Halton points in neighbouring large bases line up along diagonals, so the digits are
+// Owen scrambled with a seed per pixel and dimension, which also decorrelates the pixels.
+pub struct HaltonSampler {
+    seed: u64,
+    pixel_hash: u64,
+    sample_index: u32,
+    dimension: u32
+}
+
+impl HaltonSampler {
+    pub fn new(seed: u64) -> HaltonSampler {
+        HaltonSampler {
+            seed,
+            pixel_hash: 0,
+            sample_index: 0,
+            dimension: 0
+        }
+    }
+}
+
+impl Sampler for HaltonSampler {
+    fn start_sample(&mut self, pixel: (usize, usize), sample_index: u32) {
+        self.pixel_hash = hash_pixel(pixel, self.seed);
+        self.sample_index = sample_index;
+        self.dimension = 0;
+    }
+
+    fn get_1d(&mut self) -> f64 {
+        let dimension = self.dimension as usize;
+        self.dimension += 1;
+
+        let seed = hash_values(&[self.pixel_hash, dimension as u64]);
+        match PRIMES.get(dimension) {
+            Some(base) => scrambled_radical_inverse(self.sample_index, *base, seed),
+            None => to_unit_float(hash_values(&[seed, self.sample_index as u64]))
+        }
+    }
+
+    fn get_2d(&mut self) -> (f64, f64) {
+        (self.get_1d(), self.get_1d())
+    }
+}
+
+// Mirrors the digits of index in base around the decimal point, permuting every digit depending on the
+// digits before it. Leading zeros are permuted too, until the digits fall below double precision.
+fn scrambled_radical_inverse(index: u32, base: u32, seed: u64) -> f64 {
+    let inverse_base = 1.0 / base as f64;
+    let mut index = index;
+    let mut digits = 0_u64;
+    let mut scale = 1.0;
+    while 1.0 - scale * inverse_base < 1.0 {
+        let digit_seed = hash_values(&[seed, digits]);
+        let digit = permute(index % base, base, digit_seed as u32);
+        digits = digits * base as u64 + digit as u64;
+        scale *= inverse_base;
+        index /= base;
+    }
+
+    (digits as f64 * scale).min(1.0 - f64::EPSILON)
+}
@@ -0,0 +1,38 @@
+
+use crate::math::hash::{hash_values, to_unit_float};
+use crate::sampler::{Sampler, hash_pixel};
+
+
+// Hashes the pixel, the sample index and the dimension, so every number is random but repeatable.
+pub struct IndependentSampler {
+    seed: u64,
+    sample_hash: u64,
+    dimension: u32
+}
+
+impl IndependentSampler {
+    pub fn new(seed: u64) -> IndependentSampler {
+        IndependentSampler {
+            seed,
+            sample_hash: 0,
+            dimension: 0
+        }
+    }
+}
+
+impl Sampler for IndependentSampler {
+    fn start_sample(&mut self, pixel: (usize, usize), sample_index: u32) {
+        self.sample_hash = hash_values(&[hash_pixel(pixel, self.seed), sample_index as u64]);
+        self.dimension = 0;
+    }
+
+    fn get_1d(&mut self) -> f64 {
+        let hash = hash_values(&[self.sample_hash, self.dimension as u64]);
+        self.dimension += 1;
+        to_unit_float(hash)
+    }
+
+    fn get_2d(&mut self) -> (f64, f64) {
+        (self.get_1d(), self.get_1d())
+    }
+}
@@ -0,0 +1,109 @@
+
+pub mod independent;
+pub mod stratified;
+pub mod halton;
+pub mod sobol;
+
+#[cfg(test)]
+mod tests;
+
+use crate::math::hash::mix_bits;
+use crate::sampler::independent::IndependentSampler;
+use crate::sampler::stratified::StratifiedSampler;
+use crate::sampler::halton::HaltonSampler;
+use crate::sampler::sobol::SobolSampler;
+
+
+// Hands out the random numbers of one camera sample. Every call to get_1d or get_2d moves on to the
+// next dimension, so the camera, the lights and the materials each draw from their own dimensions.
+pub trait Sampler {
+    // sample_index counts the samples of pixel from zero, it has to be called before every camera ray
+    fn start_sample(&mut self, pixel: (usize, usize), sample_index: u32);
+
+    // uniform in [0, 1)
+    fn get_1d(&mut self) -> f64;
+
+    fn get_2d(&mut self) -> (f64, f64);
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum SamplerType {
+    // a fresh random number for every dimension, what every other sampler is compared against
+    Independent,
+    // one jittered stratum per sample, needs to know the sample count up front
+    Stratified,
+    Halton,
+    // Owen scrambled Sobol points
+    Sobol
+}
+
+pub const SAMPLERS: [(&str, SamplerType); 4] = [
+    ("independent", SamplerType::Independent),
+    ("stratified", SamplerType::Stratified),
+    ("halton", SamplerType::Halton),
+    ("sobol", SamplerType::Sobol)
+];
+
+impl SamplerType {
+    pub fn get_name(&self) -> &'static str {
+        match SAMPLERS.iter().find(|(_, sampler_type)| sampler_type == self) {
+            Some((name, _)) => name,
+            None => "independent"
+        }
+    }
+
+    // Every render thread makes its own sampler, sample_count is the number of samples per pixel.
+    // The numbers only depend on seed, the pixel, the sample index and the dimension, never on the thread.
+    pub fn create(&self, sample_count: u32, seed: u64) -> Box<dyn Sampler> {
+        match self {
+            SamplerType::Independent => Box::new(IndependentSampler::new(seed)),
+            SamplerType::Stratified => Box::new(StratifiedSampler::new(sample_count, seed)),
+            SamplerType::Halton => Box::new(HaltonSampler::new(seed)),
+            SamplerType::Sobol => Box::new(SobolSampler::new(seed))
+        }
+    }
+}
+
+// Hash of a pixel under the render seed, the same for every sample of it, samplers use it to decorrelate pixels.
+pub fn hash_pixel(pixel: (usize, usize), seed: u64) -> u64 {
+    mix_bits(seed ^ mix_bits((pixel.0 as u64) << 32 | pixel.1 as u64))
+}
+
+// Kensler's hashed permutation, maps index in [0, length) to a unique position for every seed.
+pub fn permute(index: u32, length: u32, seed: u32) -> u32 {
+    let mut mask = length - 1;
+    mask |= mask >> 1;
+    mask |= mask >> 2;
+    mask |= mask >> 4;
+    mask |= mask >> 8;
+    mask |= mask >> 16;
+
+    // cycle walking, positions outside the length are permuted again until they land inside
+    let mut value = index;
+    loop {
+        value ^= seed;
+        value = value.wrapping_mul(0xe170_893d);
+        value ^= seed >> 16;
+        value ^= (value & mask) >> 4;
+        value ^= seed >> 8;
+        value = value.wrapping_mul(0x0929_eb3f);
+        value ^= seed >> 23;
+        value ^= (value & mask) >> 1;
+        value = value.wrapping_mul(1 | seed >> 27);
+        value = value.wrapping_mul(0x6935_fa69);
+        value ^= (value & mask) >> 11;
+        value = value.wrapping_mul(0x74dc_b303);
+        value ^= (value & mask) >> 2;
+        value = value.wrapping_mul(0x9e50_1cc3);
+        value ^= (value & mask) >> 2;
+        value = value.wrapping_mul(0xc860_a3df);
+        value &= mask;
+        value ^= value >> 5;
+        if value < length {
+            break;
+        }
+    }
+
+    // in 64 bits, a wrapped sum would send two indices to the same position
+    ((value as u64 + seed as u64) % length as u64) as u32
+}
@@ -0,0 +1,87 @@
+
+use crate::math::hash::hash_values;
+use crate::sampler::{Sampler, hash_pixel};
+
+
+// Owen scrambled Sobol points padded from two dimensions (Burley 2020).
+// Every get_2d uses the first two Sobol dimensions, which are well stratified against each other,
+// with its own scramble and its own shuffled sample order so different calls stay uncorrelated.
+// Scrambling keeps the stratification of every power of two prefix of the samples.
+pub struct SobolSampler {
+    seed: u64,
+    pixel_hash: u64,
+    sample_index: u32,
+    dimension: u32
+}
+
+impl SobolSampler {
+    pub fn new(seed: u64) -> SobolSampler {
+        SobolSampler {
+            seed,
+            pixel_hash: 0,
+            sample_index: 0,
+            dimension: 0
+        }
+    }
+
+    // the sample index in the order this dimension visits the points and the seed of its scramble
+    fn next_index(&mut self) -> (u32, u32) {
+        let seed = hash_values(&[self.pixel_hash, self.dimension as u64]);
+        self.dimension += 1;
+        (nested_uniform_scramble(self.sample_index, seed as u32), (seed >> 32) as u32)
+    }
+}
+
+impl Sampler for SobolSampler {
+    fn start_sample(&mut self, pixel: (usize, usize), sample_index: u32) {
+        self.pixel_hash = hash_pixel(pixel, self.seed);
+        self.sample_index = sample_index;
+        self.dimension = 0;
+    }
+
+    fn get_1d(&mut self) -> f64 {
+        let (index, seed) = self.next_index();
+        to_unit_float(nested_uniform_scramble(index.reverse_bits(), seed))
+    }
+
+    fn get_2d(&mut self) -> (f64, f64) {
+        let (index, seed) = self.next_index();
+        let x = nested_uniform_scramble(index.reverse_bits(), seed);
+        let y = nested_uniform_scramble(sobol_second_dimension(index), seed.wrapping_mul(0x9e37_79b9) ^ 0x5bd1_e995);
+        (to_unit_float(x), to_unit_float(y))
+    }
+}
+
+// The second Sobol dimension, its direction numbers follow the primitive polynomial x + 1.
+fn sobol_second_dimension(index: u32) -> u32 {
+    let mut result = 0;
+    let mut direction = 1_u32 << 31;
+    let mut index = index;
+    while index > 0 {
+        if index & 1 == 1 {
+            result ^= direction;
+        }
+        direction ^= direction >> 1;
+        index >>= 1;
+    }
+    result
+}
+
+// Owen scrambling, every bit is flipped depending on a hash of the bits above it.
+fn nested_uniform_scramble(value: u32, seed: u32) -> u32 {
+    laine_karras_permutation(value.reverse_bits(), seed).reverse_bits()
+}
+
+// Only ever flips a bit based on the bits below it, which is Owen scrambling when applied to reversed bits.
+fn laine_karras_permutation(value: u32, seed: u32) -> u32 {
+    let mut value = value.wrapping_add(seed);
+    value ^= value.wrapping_mul(0x6c50_b47c);
+    value ^= value.wrapping_mul(0xb82f_1e52);
+    value ^= value.wrapping_mul(0xc7af_e638);
+    value ^= value.wrapping_mul(0x8d22_f6e6);
+    value
+}
+
+fn to_unit_float(value: u32) -> f64 {
+    value as f64 / (1u64 << 32) as f64
+}
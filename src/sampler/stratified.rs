@@ -0,0 +1,74 @@
+
+use crate::math::hash::{hash_values, to_unit_float};
+use crate::sampler::{Sampler, hash_pixel, permute};
+
+
+// Splits every dimension into as many strata as there are samples and gives each sample its own one.
+// Two dimensional strata form a grid, the order the strata are visited in is shuffled per pixel and
+// dimension so dimensions don't line up with each other.
+pub struct StratifiedSampler {
+    seed: u64,
+    sample_count: u32,
+    grid_size: (u32, u32),
+    pixel_hash: u64,
+    sample_index: u32,
+    dimension: u32
+}
+
+impl StratifiedSampler {
+    pub fn new(sample_count: u32, seed: u64) -> StratifiedSampler {
+        let sample_count = sample_count.max(1);
+        // as square as possible, cells left over at the end of the grid are never used
+        let width = (sample_count as f64).sqrt() as u32;
+        let height = sample_count.div_ceil(width);
+
+        StratifiedSampler {
+            seed,
+            sample_count,
+            grid_size: (width, height),
+            pixel_hash: 0,
+            sample_index: 0,
+            dimension: 0
+        }
+    }
+
+    fn next_hash(&mut self) -> u64 {
+        let hash = hash_values(&[self.pixel_hash, self.dimension as u64]);
+        self.dimension += 1;
+        hash
+    }
+}
+
+impl Sampler for StratifiedSampler {
+    fn start_sample(&mut self, pixel: (usize, usize), sample_index: u32) {
+        self.pixel_hash = hash_pixel(pixel, self.seed);
+        self.sample_index = sample_index;
+        self.dimension = 0;
+    }
+
+    fn get_1d(&mut self) -> f64 {
+        let hash = self.next_hash();
+        let jitter = to_unit_float(hash_values(&[hash, self.sample_index as u64]));
+        if self.sample_index >= self.sample_count {
+            return jitter;
+        }
+
+        let stratum = permute(self.sample_index, self.sample_count, hash as u32);
+        (stratum as f64 + jitter) / self.sample_count as f64
+    }
+
+    fn get_2d(&mut self) -> (f64, f64) {
+        let hash = self.next_hash();
+        let jitter_hash = hash_values(&[hash, self.sample_index as u64]);
+        let jitter = (to_unit_float(jitter_hash), to_unit_float(jitter_hash.rotate_left(32)));
+        let (width, height) = self.grid_size;
+        if self.sample_index >= width * height {
+            return jitter;
+        }
+
+        let cell = permute(self.sample_index, width * height, hash as u32);
+        let x = ((cell % width) as f64 + jitter.0) / width as f64;
+        let y = ((cell / width) as f64 + jitter.1) / height as f64;
+        (x, y)
+    }
+}
@@ -0,0 +1,123 @@
+
+// Sampler points for one pixel: strata that are each covered exactly once, values in [0, 1) and samples
+// that depend only on the seed, the pixel, the sample index and the dimension.
+
+use std::collections::HashSet;
+
+use crate::sampler::{Sampler, SamplerType, SAMPLERS, permute};
+
+
+const PIXEL: (usize, usize) = (17, 5);
+const SEED: u64 = 42;
+
+
+// the dimension-th value of every sample of PIXEL
+fn get_points_1d(sampler: &mut dyn Sampler, sample_count: u32, dimension: u32) -> Vec<f64> {
+    (0 .. sample_count).map(|sample_index| {
+        sampler.start_sample(PIXEL, sample_index);
+        for _ in 0 .. dimension {
+            sampler.get_1d();
+        }
+        sampler.get_1d()
+    }).collect()
+}
+
+fn get_points_2d(sampler: &mut dyn Sampler, sample_count: u32) -> Vec<(f64, f64)> {
+    (0 .. sample_count).map(|sample_index| {
+        sampler.start_sample(PIXEL, sample_index);
+        sampler.get_2d()
+    }).collect()
+}
+
+fn assert_strata_covered_1d(points: &[f64], strata: usize, name: &str) {
+    let covered: HashSet<usize> = points.iter().map(|point| (point * strata as f64) as usize).collect();
+    assert_eq!(covered.len(), strata, "{} leaves strata empty: {:?}", name, points);
+}
+
+fn assert_strata_covered_2d(points: &[(f64, f64)], grid: (usize, usize), name: &str) {
+    let covered: HashSet<(usize, usize)> = points.iter()
+        .map(|point| ((point.0 * grid.0 as f64) as usize, (point.1 * grid.1 as f64) as usize))
+        .collect();
+    assert_eq!(covered.len(), grid.0 * grid.1, "{} leaves cells of {:?} empty: {:?}", name, grid, points);
+}
+
+#[test]
+fn permute_maps_to_every_position_once() {
+    for length in [1, 2, 5, 16, 37, 100] {
+        for seed in [0, 1, 0xdead_beef, u32::MAX] {
+            let positions: HashSet<u32> = (0 .. length).map(|index| permute(index, length, seed)).collect();
+            assert_eq!(positions.len(), length as usize, "length {} seed {}", length, seed);
+            assert!(positions.iter().all(|position| *position < length));
+        }
+    }
+}
+
+#[test]
+fn stratified_points_cover_every_stratum() {
+    let mut sampler = SamplerType::Stratified.create(16, SEED);
+    for dimension in 0 .. 4 {
+        assert_strata_covered_1d(&get_points_1d(sampler.as_mut(), 16, dimension), 16, "stratified");
+    }
+    assert_strata_covered_2d(&get_points_2d(sampler.as_mut(), 16), (4, 4), "stratified");
+
+    // 10 samples use a 3x4 grid, no two of them share a cell
+    let mut sampler = SamplerType::Stratified.create(10, SEED);
+    let points = get_points_2d(sampler.as_mut(), 10);
+    let cells: HashSet<(usize, usize)> = points.iter().map(|point| ((point.0 * 3.0) as usize, (point.1 * 4.0) as usize)).collect();
+    assert_eq!(cells.len(), 10);
+}
+
+#[test]
+fn halton_points_cover_every_stratum_of_their_base() {
+    let mut sampler = SamplerType::Halton.create(0, SEED);
+    assert_strata_covered_1d(&get_points_1d(sampler.as_mut(), 16, 0), 16, "halton base 2");
+    assert_strata_covered_1d(&get_points_1d(sampler.as_mut(), 27, 1), 27, "halton base 3");
+    assert_strata_covered_1d(&get_points_1d(sampler.as_mut(), 25, 2), 25, "halton base 5");
+    assert_strata_covered_2d(&get_points_2d(sampler.as_mut(), 6), (2, 3), "halton");
+    assert_strata_covered_2d(&get_points_2d(sampler.as_mut(), 36), (4, 9), "halton");
+}
+
+#[test]
+fn sobol_points_cover_every_elementary_interval() {
+    let mut sampler = SamplerType::Sobol.create(0, SEED);
+    assert_strata_covered_1d(&get_points_1d(sampler.as_mut(), 16, 0), 16, "sobol");
+    assert_strata_covered_1d(&get_points_1d(sampler.as_mut(), 32, 3), 32, "sobol");
+    let points = get_points_2d(sampler.as_mut(), 16);
+    for grid in [(1, 16), (2, 8), (4, 4), (8, 2), (16, 1)] {
+        assert_strata_covered_2d(&points, grid, "sobol");
+    }
+}
+
+#[test]
+fn values_are_in_the_unit_interval() {
+    for (name, sampler_type) in SAMPLERS {
+        let mut sampler = sampler_type.create(64, SEED);
+        for sample_index in 0 .. 100 {
+            sampler.start_sample(PIXEL, sample_index);
+            // past the last stratum, prime and padded dimension
+            for _ in 0 .. 70 {
+                let value = sampler.get_1d();
+                let (x, y) = sampler.get_2d();
+                assert!([value, x, y].iter().all(|value| (0.0 .. 1.0).contains(value)), "{} gave {}, {}, {}", name, value, x, y);
+            }
+        }
+    }
+}
+
+#[test]
+fn samples_depend_only_on_seed_pixel_index_and_dimension() {
+    for (name, sampler_type) in SAMPLERS {
+        let get_values = |seed: u64, pixel: (usize, usize), sample_index: u32| {
+            let mut sampler = sampler_type.create(16, seed);
+            sampler.start_sample((0, 0), 7);
+            sampler.get_2d();
+            sampler.start_sample(pixel, sample_index);
+            (sampler.get_1d(), sampler.get_2d(), sampler.get_1d())
+        };
+
+        let first = get_values(SEED, PIXEL, 3);
+        assert!(first == get_values(SEED, PIXEL, 3), "{} is not repeatable", name);
+        assert!(first != get_values(SEED, (PIXEL.0 + 1, PIXEL.1), 3), "{} repeats across pixels", name);
+        assert!(first != get_values(SEED + 1, PIXEL, 3), "{} ignores the seed", name);
+    }
+}
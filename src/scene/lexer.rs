@@ -0,0 +1,133 @@
+
+use crate::scene::SceneError;
+
+
+#[derive(Clone, PartialEq)]
+pub enum TokenKind {
+    Identifier(String),
+    // the source text is kept for values a f64 can not hold exactly, like large seeds
+    Number(f64, String),
+    Text(String),
+    OpenBrace,
+    CloseBrace,
+    End
+}
+
+#[derive(Clone)]
+pub struct Token {
+    pub kind: TokenKind,
+    pub line: usize,
+    pub column: usize
+}
+
+impl Token {
+    pub fn describe(&self) -> String {
+        match &self.kind {
+            TokenKind::Identifier(name) => format!("'{}'", name),
+            TokenKind::Number(number, _) => format!("number {}", number),
+            TokenKind::Text(text) => format!("string \"{}\"", text),
+            TokenKind::OpenBrace => "'{'".to_string(),
+            TokenKind::CloseBrace => "'}'".to_string(),
+            TokenKind::End => "end of file".to_string()
+        }
+    }
+}
+
+pub fn tokenize(source: &str) -> Result<Vec<Token>, SceneError> {
+    let characters: Vec<char> = source.chars().collect();
+    let mut tokens: Vec<Token> = Vec::new();
+
+    let mut index = 0;
+    let mut line = 1;
+    let mut column = 1;
+    while index < characters.len() {
+        let character = characters[index];
+        let start_line = line;
+        let start_column = column;
+
+        if character == '\n' {
+            index += 1;
+            line += 1;
+            column = 1;
+            continue;
+        }
+
+        if character.is_whitespace() {
+            index += 1;
+            column += 1;
+            continue;
+        }
+
+        if character == '#' {
+            while index < characters.len() && characters[index] != '\n' {
+                index += 1;
+            }
+            continue;
+        }
+
+        let kind = if character == '{' {
+            index += 1;
+            column += 1;
+            TokenKind::OpenBrace
+        } else if character == '}' {
+            index += 1;
+            column += 1;
+            TokenKind::CloseBrace
+        } else if character == '"' {
+            index += 1;
+            column += 1;
+            let mut text = String::new();
+            loop {
+                if index >= characters.len() || characters[index] == '\n' {
+                    return Err(SceneError::new(start_line, start_column, "unterminated string"));
+                }
+
+                let current = characters[index];
+                index += 1;
+                column += 1;
+                if current == '"' {
+                    break;
+                }
+                text.push(current);
+            }
+            TokenKind::Text(text)
+        } else if character.is_ascii_digit() || character == '-' || character == '+' || character == '.' {
+            let mut text = String::new();
+            while index < characters.len() {
+                let current = characters[index];
+                let is_exponent_sign = (current == '-' || current == '+') && text.ends_with(['e', 'E']);
+                if current.is_ascii_alphanumeric() || current == '.' || is_exponent_sign || text.is_empty() {
+                    text.push(current);
+                    index += 1;
+                    column += 1;
+                } else {
+                    break;
+                }
+            }
+
+            match text.parse::<f64>() {
+                Ok(number) if number.is_finite() => TokenKind::Number(number, text),
+                _ => {
+                    let message = format!("invalid number '{}'", text);
+                    return Err(SceneError::new(start_line, start_column, &message));
+                }
+            }
+        } else if character.is_alphabetic() || character == '_' {
+            let mut text = String::new();
+            while index < characters.len() && (characters[index].is_alphanumeric() || characters[index] == '_') {
+                text.push(characters[index]);
+                index += 1;
+                column += 1;
+            }
+            TokenKind::Identifier(text)
+        } else {
+            let message = format!("unexpected character '{}'", character);
+            return Err(SceneError::new(start_line, start_column, &message));
+        };
+
+        tokens.push(Token { kind, line: start_line, column: start_column });
+    }
+
+    tokens.push(Token { kind: TokenKind::End, line, column });
+    Ok(tokens)
+}
@@ -0,0 +1,102 @@
+
+pub mod lexer;
+pub mod parser;
+
+#[cfg(test)]
+mod tests;
+
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use crate::camera::CameraSettings;
+use crate::math::vec3::Point3;
+use crate::raytracer::RayTracerSettings;
+use crate::world::World;
+use self::parser::SceneParser;
+
+
+#[derive(Clone, Copy)]
+pub struct SceneCamera {
+    pub look_from: Point3,
+    pub look_to: Point3,
+    pub settings: CameraSettings
+}
+
+impl SceneCamera {
+    pub fn new_default() -> SceneCamera {
+        SceneCamera {
+            look_from: Point3::new(13.0, 2.0, 3.0),
+            look_to: Point3::new(0.0, 0.0, 0.0),
+            settings: CameraSettings::new(20.0, 0.1, 10.0)
+        }
+    }
+}
+
+pub struct Scene {
+    pub world: World,
+    pub camera: SceneCamera,
+    pub settings: RayTracerSettings
+}
+
+pub struct SceneError {
+    pub path: PathBuf,
+    pub line: usize,
+    pub column: usize,
+    pub message: String
+}
+
+impl SceneError {
+    pub fn new(line: usize, column: usize, message: &str) -> SceneError {
+        SceneError {
+            path: PathBuf::new(),
+            line,
+            column,
+            message: message.to_string()
+        }
+    }
+}
+
+impl fmt::Display for SceneError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // errors that are not tied to a position, like a missing file, have no line
+        if self.line == 0 {
+            return write!(f, "{path}: {message}", path = self.path.display(), message = self.message);
+        }
+
+        write!(f, "{path}:{line}:{column}: {message}",
+            path = self.path.display(),
+            line = self.line,
+            column = self.column,
+            message = self.message)
+    }
+}
+
+impl fmt::Debug for SceneError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(self, f)
+    }
+}
+
+pub fn load_scene(path: &Path) -> Result<Scene, SceneError> {
+    let source = match std::fs::read_to_string(path) {
+        Ok(source) => source,
+        Err(error) => {
+            let mut scene_error = SceneError::new(0, 0, &error.to_string());
+            scene_error.path = path.to_path_buf();
+            return Err(scene_error);
+        }
+    };
+
+    parse_scene(path, &source)
+}
+
+// path is only used for error messages and to resolve the files a scene references.
+pub fn parse_scene(path: &Path, source: &str) -> Result<Scene, SceneError> {
+    let base_directory = path.parent().unwrap_or_else(|| Path::new("")).to_path_buf();
+    let result = lexer::tokenize(source).and_then(|tokens| SceneParser::new(tokens, base_directory).parse());
+
+    result.map_err(|mut error| {
+        error.path = path.to_path_buf();
+        error
+    })
+}
@@ -0,0 +1,693 @@
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use crate::camera::CameraSettings;
+use crate::import::obj::load_obj;
+use crate::texture::Texture;
+use crate::texture::solid_color::SolidColor;
+use crate::texture::checker::{UvChecker, SpatialChecker};
+use crate::texture::image_texture::{ImageTexture, WrapMode, FilterMode};
+use crate::texture::noise::{NoiseTexture, MarbleTexture, WoodTexture, CellularTexture};
+use crate::material::Material;
+use crate::material::lambertian::Lambertian;
+use crate::material::metal::Metal;
+use crate::material::dielectric::Dielectric;
+use crate::material::conductor::{Conductor, ConductorPreset, CONDUCTOR_PRESETS};
+use crate::material::diffuse_light::DiffuseLight;
+use crate::material::identified::IdentifiedMaterial;
+use crate::math::vec3::{Vec3, Point3, Color};
+use crate::object::sphere::Sphere;
+use crate::object::triangle::Triangle;
+use crate::object::triangle_mesh::{TriangleMesh, MeshFace};
+use crate::raytracer::RayTracerSettings;
+use crate::threading::tile::TileOrder;
+use crate::display::TONE_MAPPERS;
+use crate::sampler::SAMPLERS;
+use crate::denoise::DenoiseMode;
+use crate::scene::{Scene, SceneCamera, SceneError};
+use crate::scene::lexer::{Token, TokenKind};
+use crate::world::World;
+
+
+pub struct SceneParser {
+    tokens: Vec<Token>,
+    position: usize,
+    base_directory: PathBuf,
+    textures: HashMap<String, Box<dyn Texture>>,
+    materials: HashMap<String, Box<dyn Material>>,
+    // ids for the material id pass, OBJ materials are keyed by file and name
+    material_ids: HashMap<String, u32>,
+    world: World,
+    camera: SceneCamera,
+    settings: RayTracerSettings
+}
+
+impl SceneParser {
+    pub fn new(tokens: Vec<Token>, base_directory: PathBuf) -> SceneParser {
+        SceneParser {
+            tokens,
+            position: 0,
+            base_directory,
+            textures: HashMap::new(),
+            materials: HashMap::new(),
+            material_ids: HashMap::new(),
+            world: World::new_default(),
+            camera: SceneCamera::new_default(),
+            settings: RayTracerSettings::new_default()
+        }
+    }
+
+    pub fn parse(mut self) -> Result<Scene, SceneError> {
+        loop {
+            let token = self.next_token();
+            match &token.kind {
+                TokenKind::End => break,
+                TokenKind::Identifier(keyword) => match keyword.as_str() {
+                    "settings" => self.parse_settings()?,
+                    "camera" => self.parse_camera(&token)?,
+                    "background" => {
+                        let color = self.expect_vec3()?;
+                        self.world.set_sky_color(color);
+                    }
+                    "texture" => self.parse_texture()?,
+                    "material" => self.parse_material()?,
+                    "sphere" => self.parse_sphere(&token)?,
+                    "triangle" => self.parse_triangle(&token)?,
+                    "quad" => self.parse_quad(&token)?,
+                    "mesh" => self.parse_mesh(&token)?,
+                    _ => {
+                        return Err(error_at(&token, &format!("unknown statement '{}'", keyword)));
+                    }
+                },
+                _ => {
+                    return Err(error_at(&token, &format!("expected a statement, found {}", token.describe())));
+                }
+            }
+        }
+
+        Ok(Scene {
+            world: self.world,
+            camera: self.camera,
+            settings: self.settings
+        })
+    }
+
+    fn parse_settings(&mut self) -> Result<(), SceneError> {
+        self.parse_block("settings", |parser, key| {
+            match key {
+                "sample_count" => parser.settings.sample_count = parser.expect_count(1)?,
+                "bound_limit" => parser.settings.bound_limit = parser.expect_count(1)?,
+                "display_rate" => parser.settings.display_rate = parser.expect_count(1)?,
+                "tile_size" => parser.settings.tile_size = parser.expect_count(1)? as usize,
+                "tile_order" => {
+                    parser.settings.tile_order = parser.expect_keyword(&[
+                        ("scanline", TileOrder::Scanline),
+                        ("spiral", TileOrder::Spiral),
+                        ("hilbert", TileOrder::Hilbert)
+                    ])?;
+                }
+                "exposure" => parser.settings.display.exposure = parser.expect_number()?,
+                "dither" => parser.settings.display.dither = parser.expect_keyword(&[("on", true), ("off", false)])?,
+                "tone_mapper" => parser.settings.display.tone_mapper = parser.expect_keyword(&TONE_MAPPERS)?,
+                "white_point" => parser.settings.display.white_point = parser.expect_number_in(1e-3, f64::MAX)?,
+                "denoise" => {
+                    parser.settings.denoise = parser.expect_keyword(&[
+                        ("off", DenoiseMode::Off),
+                        ("final", DenoiseMode::Final),
+                        ("progressive", DenoiseMode::Progressive)
+                    ])?;
+                }
+                "denoise_iterations" => parser.settings.denoise_iterations = parser.expect_count(1)?,
+                "adaptive_threshold" => parser.settings.adaptive_threshold = parser.expect_number_in(0.0, f64::MAX)?,
+                "min_sample_count" => parser.settings.min_sample_count = parser.expect_count(2)?,
+                "sampler" => parser.settings.sampler = parser.expect_keyword(&SAMPLERS)?,
+                "seed" => parser.settings.seed = parser.expect_seed()?,
+                _ => return Ok(false)
+            }
+            Ok(true)
+        })
+    }
+
+    fn parse_camera(&mut self, statement: &Token) -> Result<(), SceneError> {
+        let mut fov = 90.0;
+        let mut aperture = 0.0;
+        let mut focus_dist = 10.0;
+        let mut look_from = self.camera.look_from;
+        let mut look_to = self.camera.look_to;
+
+        self.parse_block("camera", |parser, key| {
+            match key {
+                "look_from" => look_from = parser.expect_vec3()?,
+                "look_to" => look_to = parser.expect_vec3()?,
+                "fov" => fov = parser.expect_number_between(0.0, 180.0)?,
+                "aperture" => aperture = parser.expect_number_in(0.0, f64::MAX)?,
+                "focus_dist" => focus_dist = parser.expect_number_between(0.0, f64::MAX)?,
+                _ => return Ok(false)
+            }
+            Ok(true)
+        })?;
+
+        // the camera would have no direction to look in
+        if (look_to - look_from).is_near_zero() {
+            return Err(error_at(statement, "camera look_from and look_to are the same point"));
+        }
+
+        self.camera = SceneCamera {
+            look_from,
+            look_to,
+            settings: CameraSettings::new(fov, aperture, focus_dist)
+        };
+        Ok(())
+    }
+
+    fn parse_texture(&mut self) -> Result<(), SceneError> {
+        let name = self.expect_identifier()?;
+        let type_token = self.next_token();
+        let texture_type = match &type_token.kind {
+            TokenKind::Identifier(texture_type) => texture_type.clone(),
+            _ => {
+                return Err(error_at(&type_token, &format!("expected a texture type, found {}", type_token.describe())));
+            }
+        };
+
+        let texture: Box<dyn Texture> = match texture_type.as_str() {
+            "solid" => {
+                let mut color = Color::new(0.5, 0.5, 0.5);
+                self.parse_block("solid", |parser, key| {
+                    match key {
+                        "color" => color = parser.expect_vec3()?,
+                        _ => return Ok(false)
+                    }
+                    Ok(true)
+                })?;
+                Box::new(SolidColor::new(color))
+            }
+            "checker" | "checker_3d" => {
+                let mut odd: Box<dyn Texture> = Box::new(SolidColor::new(Color::new(0.0, 0.0, 0.0)));
+                let mut even: Box<dyn Texture> = Box::new(SolidColor::new(Color::new(1.0, 1.0, 1.0)));
+                let mut scale = if texture_type == "checker" { 10.0 } else { 1.0 };
+                self.parse_block(&texture_type, |parser, key| {
+                    match key {
+                        "odd" => odd = parser.expect_texture()?,
+                        "even" => even = parser.expect_texture()?,
+                        "scale" => scale = parser.expect_number_in(f64::MIN_POSITIVE, f64::MAX)?,
+                        _ => return Ok(false)
+                    }
+                    Ok(true)
+                })?;
+
+                if texture_type == "checker" {
+                    Box::new(UvChecker::new(odd, even, scale))
+                } else {
+                    Box::new(SpatialChecker::new(odd, even, scale))
+                }
+            }
+            "image" => {
+                let mut file: Option<(Token, String)> = None;
+                let mut wrap_mode = WrapMode::Repeat;
+                let mut filter_mode = FilterMode::Trilinear;
+                let mut is_srgb = true;
+                self.parse_block("image", |parser, key| {
+                    match key {
+                        "file" => {
+                            let token = parser.peek_token().clone();
+                            file = Some((token, parser.expect_text()?));
+                        }
+                        "wrap" => {
+                            wrap_mode = parser.expect_keyword(&[
+                                ("repeat", WrapMode::Repeat),
+                                ("clamp", WrapMode::Clamp),
+                                ("mirror", WrapMode::Mirror)
+                            ])?;
+                        }
+                        "filter" => {
+                            filter_mode = parser.expect_keyword(&[
+                                ("nearest", FilterMode::Nearest),
+                                ("bilinear", FilterMode::Bilinear),
+                                ("trilinear", FilterMode::Trilinear)
+                            ])?;
+                        }
+                        "color_space" => is_srgb = parser.expect_keyword(&[("srgb", true), ("linear", false)])?,
+                        _ => return Ok(false)
+                    }
+                    Ok(true)
+                })?;
+
+                let (file_token, file_name) = require(&type_token, "image", "file", file)?;
+                let mut image_texture = match ImageTexture::load(&self.base_directory.join(&file_name), is_srgb) {
+                    Ok(image_texture) => image_texture,
+                    Err(import_error) => {
+                        return Err(error_at(&file_token, &format!("failed to load image: {}", import_error)));
+                    }
+                };
+                image_texture.set_wrap_mode(wrap_mode);
+                image_texture.set_filter_mode(filter_mode);
+                Box::new(image_texture)
+            }
+            "noise" | "marble" | "wood" | "cellular" => {
+                let mut low: Box<dyn Texture> = Box::new(SolidColor::new(Color::new(0.0, 0.0, 0.0)));
+                let mut high: Box<dyn Texture> = Box::new(SolidColor::new(Color::new(1.0, 1.0, 1.0)));
+                let mut scale = 1.0;
+                let mut octaves = match texture_type.as_str() {
+                    "marble" => 7,
+                    "wood" => 3,
+                    _ => 1
+                };
+                let mut seed = 0;
+                let has_octaves = texture_type != "cellular";
+                self.parse_block(&texture_type, |parser, key| {
+                    match key {
+                        "low" => low = parser.expect_texture()?,
+                        "high" => high = parser.expect_texture()?,
+                        "scale" => scale = parser.expect_number_in(f64::MIN_POSITIVE, f64::MAX)?,
+                        "octaves" if has_octaves => octaves = parser.expect_count(1)?,
+                        "seed" => seed = parser.expect_count(0)?,
+                        _ => return Ok(false)
+                    }
+                    Ok(true)
+                })?;
+
+                let seed = seed as u64;
+                match texture_type.as_str() {
+                    "noise" => Box::new(NoiseTexture::new(low, high, scale, octaves, seed)),
+                    "marble" => Box::new(MarbleTexture::new(low, high, scale, octaves, seed)),
+                    "wood" => Box::new(WoodTexture::new(low, high, scale, octaves, seed)),
+                    _ => Box::new(CellularTexture::new(low, high, scale, seed))
+                }
+            }
+            _ => {
+                return Err(error_at(&type_token, &format!("unknown texture type '{}'", texture_type)));
+            }
+        };
+
+        self.textures.insert(name, texture);
+        Ok(())
+    }
+
+    fn parse_material(&mut self) -> Result<(), SceneError> {
+        let name = self.expect_identifier()?;
+        let type_token = self.next_token();
+        let material_type = match &type_token.kind {
+            TokenKind::Identifier(material_type) => material_type.clone(),
+            _ => {
+                return Err(error_at(&type_token, &format!("expected a material type, found {}", type_token.describe())));
+            }
+        };
+
+        let material: Box<dyn Material> = match material_type.as_str() {
+            "lambertian" => {
+                let mut albedo: Box<dyn Texture> = Box::new(SolidColor::new(Color::new(0.5, 0.5, 0.5)));
+                self.parse_block("lambertian", |parser, key| {
+                    match key {
+                        "albedo" => albedo = parser.expect_texture()?,
+                        _ => return Ok(false)
+                    }
+                    Ok(true)
+                })?;
+                Box::new(Lambertian::new_with_texture(albedo))
+            }
+            "metal" => {
+                let mut albedo: Box<dyn Texture> = Box::new(SolidColor::new(Color::new(0.5, 0.5, 0.5)));
+                let mut fuzziness: Box<dyn Texture> = Box::new(SolidColor::new(Color::new(0.0, 0.0, 0.0)));
+                self.parse_block("metal", |parser, key| {
+                    match key {
+                        "albedo" => albedo = parser.expect_texture()?,
+                        "fuzziness" => fuzziness = parser.expect_scalar_texture(0.0, 1.0)?,
+                        _ => return Ok(false)
+                    }
+                    Ok(true)
+                })?;
+                Box::new(Metal::new_with_textures(albedo, fuzziness))
+            }
+            "conductor" => {
+                let (mut eta, mut k) = ConductorPreset::Aluminum.get_ior();
+                let mut roughness_u: Box<dyn Texture> = Box::new(SolidColor::new(Color::new(0.2, 0.2, 0.2)));
+                let mut roughness_v: Box<dyn Texture> = Box::new(SolidColor::new(Color::new(0.2, 0.2, 0.2)));
+                self.parse_block("conductor", |parser, key| {
+                    match key {
+                        "preset" => (eta, k) = parser.expect_keyword(&CONDUCTOR_PRESETS)?.get_ior(),
+                        "eta" => eta = parser.expect_vec3()?,
+                        "k" => k = parser.expect_vec3()?,
+                        "roughness" => {
+                            roughness_u = parser.expect_scalar_texture(0.0, 1.0)?;
+                            roughness_v = roughness_u.clone();
+                        }
+                        "roughness_u" => roughness_u = parser.expect_scalar_texture(0.0, 1.0)?,
+                        "roughness_v" => roughness_v = parser.expect_scalar_texture(0.0, 1.0)?,
+                        _ => return Ok(false)
+                    }
+                    Ok(true)
+                })?;
+                Box::new(Conductor::new_with_textures(eta, k, roughness_u, roughness_v))
+            }
+            "dielectric" => {
+                let mut refraction_index = 1.5;
+                self.parse_block("dielectric", |parser, key| {
+                    match key {
+                        "refraction_index" => refraction_index = parser.expect_number_in(f64::MIN_POSITIVE, f64::MAX)?,
+                        _ => return Ok(false)
+                    }
+                    Ok(true)
+                })?;
+                Box::new(Dielectric::new(refraction_index))
+            }
+            "diffuse_light" => {
+                let mut emit = Color::new(1.0, 1.0, 1.0);
+                self.parse_block("diffuse_light", |parser, key| {
+                    match key {
+                        "emit" => emit = parser.expect_vec3()?,
+                        _ => return Ok(false)
+                    }
+                    Ok(true)
+                })?;
+                Box::new(DiffuseLight::new(emit))
+            }
+            _ => {
+                return Err(error_at(&type_token, &format!("unknown material type '{}'", material_type)));
+            }
+        };
+
+        let material_id = self.get_material_id(&name);
+        self.materials.insert(name, Box::new(IdentifiedMaterial::new(material_id, material)));
+        Ok(())
+    }
+
+    fn parse_sphere(&mut self, statement: &Token) -> Result<(), SceneError> {
+        let mut center: Option<Point3> = None;
+        let mut radius: Option<f64> = None;
+        let mut material: Option<Box<dyn Material>> = None;
+
+        self.parse_block("sphere", |parser, key| {
+            match key {
+                "center" => center = Some(parser.expect_vec3()?),
+                "radius" => radius = Some(parser.expect_nonzero_number()?),
+                "material" => material = Some(parser.expect_material()?),
+                _ => return Ok(false)
+            }
+            Ok(true)
+        })?;
+
+        let sphere = Sphere::new(
+            require(statement, "sphere", "center", center)?,
+            require(statement, "sphere", "radius", radius)?,
+            require(statement, "sphere", "material", material)?);
+        self.world.add_object(Box::new(sphere));
+        Ok(())
+    }
+
+    fn parse_triangle(&mut self, statement: &Token) -> Result<(), SceneError> {
+        let mut vertices: [Option<Point3>; 3] = [None; 3];
+        let mut material: Option<Box<dyn Material>> = None;
+
+        self.parse_block("triangle", |parser, key| {
+            match key {
+                "v0" => vertices[0] = Some(parser.expect_vec3()?),
+                "v1" => vertices[1] = Some(parser.expect_vec3()?),
+                "v2" => vertices[2] = Some(parser.expect_vec3()?),
+                "material" => material = Some(parser.expect_material()?),
+                _ => return Ok(false)
+            }
+            Ok(true)
+        })?;
+
+        let triangle = Triangle::new(
+            [
+                require(statement, "triangle", "v0", vertices[0])?,
+                require(statement, "triangle", "v1", vertices[1])?,
+                require(statement, "triangle", "v2", vertices[2])?
+            ],
+            require(statement, "triangle", "material", material)?);
+        self.world.add_object(Box::new(triangle));
+        Ok(())
+    }
+
+    // Parallelogram spanned by u and v from origin, the front face is on the side of u x v.
+    fn parse_quad(&mut self, statement: &Token) -> Result<(), SceneError> {
+        let mut origin: Option<Point3> = None;
+        let mut u: Option<Vec3> = None;
+        let mut v: Option<Vec3> = None;
+        let mut material: Option<Box<dyn Material>> = None;
+
+        self.parse_block("quad", |parser, key| {
+            match key {
+                "origin" => origin = Some(parser.expect_vec3()?),
+                "u" => u = Some(parser.expect_vec3()?),
+                "v" => v = Some(parser.expect_vec3()?),
+                "material" => material = Some(parser.expect_material()?),
+                _ => return Ok(false)
+            }
+            Ok(true)
+        })?;
+
+        let origin = require(statement, "quad", "origin", origin)?;
+        let u = require(statement, "quad", "u", u)?;
+        let v = require(statement, "quad", "v", v)?;
+        let positions = vec![origin, origin + u, origin + u + v, origin + v];
+        let uvs = vec![(0.0, 0.0), (1.0, 0.0), (1.0, 1.0), (0.0, 1.0)];
+        let mut faces = vec![MeshFace::new([0, 1, 2]), MeshFace::new([0, 2, 3])];
+        for face in faces.iter_mut() {
+            face.uvs = Some(face.vertices);
+        }
+
+        let quad = TriangleMesh::new(positions, Vec::new(), uvs, faces, require(statement, "quad", "material", material)?);
+        self.world.add_object(Box::new(quad));
+        Ok(())
+    }
+
+    fn parse_mesh(&mut self, statement: &Token) -> Result<(), SceneError> {
+        let mut file: Option<(Token, String)> = None;
+        let mut material: Option<Box<dyn Material>> = None;
+        let mut scale = 1.0;
+        let mut offset = Vec3::new_default();
+
+        self.parse_block("mesh", |parser, key| {
+            match key {
+                "file" => {
+                    let token = parser.peek_token().clone();
+                    file = Some((token, parser.expect_text()?));
+                }
+                "material" => material = Some(parser.expect_material()?),
+                "scale" => scale = parser.expect_number_in(f64::MIN_POSITIVE, f64::MAX)?,
+                "offset" => offset = parser.expect_vec3()?,
+                _ => return Ok(false)
+            }
+            Ok(true)
+        })?;
+
+        let (file_token, file_name) = require(statement, "mesh", "file", file)?;
+        let obj_meshes = match load_obj(&self.base_directory.join(&file_name)) {
+            Ok(obj_meshes) => obj_meshes,
+            Err(import_error) => {
+                return Err(error_at(&file_token, &format!("failed to load mesh: {}", import_error)));
+            }
+        };
+
+        for mut obj_mesh in obj_meshes {
+            for position in obj_mesh.positions.iter_mut() {
+                *position = *position * scale + offset;
+            }
+
+            match &material {
+                Some(material) => obj_mesh.material = material.clone(),
+                None => {
+                    let material_id = self.get_material_id(&format!("{}:{}", file_name, obj_mesh.material_name));
+                    obj_mesh.material = Box::new(IdentifiedMaterial::new(material_id, obj_mesh.material));
+                }
+            }
+
+            self.world.add_object(Box::new(obj_mesh.into_triangle_mesh()));
+        }
+
+        Ok(())
+    }
+
+    // The same name always gets the same id, new names count up from one.
+    fn get_material_id(&mut self, key: &str) -> u32 {
+        let next_id = self.material_ids.len() as u32 + 1;
+        *self.material_ids.entry(key.to_string()).or_insert(next_id)
+    }
+
+    // Parses `{ key values ... }`, the handler reads the values of a key and returns false for unknown keys.
+    fn parse_block<F>(&mut self, block_name: &str, mut handle_property: F) -> Result<(), SceneError>
+        where F: FnMut(&mut SceneParser, &str) -> Result<bool, SceneError>
+    {
+        let open_token = self.next_token();
+        if open_token.kind != TokenKind::OpenBrace {
+            return Err(error_at(&open_token, &format!("expected '{{' after '{}', found {}", block_name, open_token.describe())));
+        }
+
+        loop {
+            let token = self.next_token();
+            match &token.kind {
+                TokenKind::CloseBrace => break,
+                TokenKind::Identifier(key) => {
+                    if !handle_property(self, key)? {
+                        return Err(error_at(&token, &format!("unknown property '{}' in {}", key, block_name)));
+                    }
+                }
+                TokenKind::End => {
+                    return Err(error_at(&open_token, &format!("'{}' block is never closed", block_name)));
+                }
+                _ => {
+                    return Err(error_at(&token, &format!("expected a property name in {}, found {}", block_name, token.describe())));
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn next_token(&mut self) -> Token {
+        let token = self.tokens[self.position].clone();
+        if self.position + 1 < self.tokens.len() {
+            self.position += 1;
+        }
+        token
+    }
+
+    fn peek_token(&self) -> &Token {
+        &self.tokens[self.position]
+    }
+
+    fn expect_number(&mut self) -> Result<f64, SceneError> {
+        let token = self.next_token();
+        match token.kind {
+            TokenKind::Number(number, _) => Ok(number),
+            _ => Err(error_at(&token, &format!("expected a number, found {}", token.describe())))
+        }
+    }
+
+    fn expect_number_in(&mut self, min: f64, max: f64) -> Result<f64, SceneError> {
+        let token = self.peek_token().clone();
+        let number = self.expect_number()?;
+        if number < min || number > max {
+            return Err(error_at(&token, &format!("{} is out of range", number)));
+        }
+        Ok(number)
+    }
+
+    // like expect_number_in, but min and max themselves are out of range
+    fn expect_number_between(&mut self, min: f64, max: f64) -> Result<f64, SceneError> {
+        let token = self.peek_token().clone();
+        let number = self.expect_number()?;
+        if number <= min || number >= max {
+            return Err(error_at(&token, &format!("{} is out of range, expected a number between {} and {}", number, min, max)));
+        }
+        Ok(number)
+    }
+
+    fn expect_nonzero_number(&mut self) -> Result<f64, SceneError> {
+        let token = self.peek_token().clone();
+        let number = self.expect_number()?;
+        if number == 0.0 {
+            return Err(error_at(&token, "expected a number other than 0"));
+        }
+        Ok(number)
+    }
+
+    fn expect_count(&mut self, min: u32) -> Result<u32, SceneError> {
+        let token = self.peek_token().clone();
+        let number = self.expect_number()?;
+        if number.fract() != 0.0 || number < min as f64 || number > u32::MAX as f64 {
+            return Err(error_at(&token, &format!("expected a whole number of at least {}, found {}", min, number)));
+        }
+        Ok(number as u32)
+    }
+
+    // Seeds use the whole u64 range like --seed, which a f64 does not hold exactly, so they are read from the source text.
+    fn expect_seed(&mut self) -> Result<u64, SceneError> {
+        let token = self.next_token();
+        let text = match &token.kind {
+            TokenKind::Number(_, text) => text,
+            _ => return Err(error_at(&token, &format!("expected a number, found {}", token.describe())))
+        };
+
+        match text.parse::<u64>() {
+            Ok(seed) => Ok(seed),
+            Err(_) => Err(error_at(&token, &format!("expected a whole number between 0 and {}, found {}", u64::MAX, text)))
+        }
+    }
+
+    fn expect_vec3(&mut self) -> Result<Vec3, SceneError> {
+        let x = self.expect_number()?;
+        let y = self.expect_number()?;
+        let z = self.expect_number()?;
+        Ok(Vec3::new(x, y, z))
+    }
+
+    fn expect_identifier(&mut self) -> Result<String, SceneError> {
+        let token = self.next_token();
+        match token.kind {
+            TokenKind::Identifier(name) => Ok(name),
+            _ => Err(error_at(&token, &format!("expected a name, found {}", token.describe())))
+        }
+    }
+
+    fn expect_text(&mut self) -> Result<String, SceneError> {
+        let token = self.next_token();
+        match token.kind {
+            TokenKind::Text(text) => Ok(text),
+            _ => Err(error_at(&token, &format!("expected a quoted string, found {}", token.describe())))
+        }
+    }
+
+    // One of a fixed set of names, each standing for a value.
+    fn expect_keyword<T: Copy>(&mut self, choices: &[(&str, T)]) -> Result<T, SceneError> {
+        let token = self.peek_token().clone();
+        let name = self.expect_identifier()?;
+        match choices.iter().find(|choice| choice.0 == name) {
+            Some(choice) => Ok(choice.1),
+            None => {
+                let names: Vec<&str> = choices.iter().map(|choice| choice.0).collect();
+                Err(error_at(&token, &format!("expected one of {}, found '{}'", names.join(", "), name)))
+            }
+        }
+    }
+
+    // Either the name of a texture or a color written in place.
+    fn expect_texture(&mut self) -> Result<Box<dyn Texture>, SceneError> {
+        let token = self.peek_token().clone();
+        match &token.kind {
+            TokenKind::Identifier(name) => {
+                self.next_token();
+                match self.textures.get(name) {
+                    Some(texture) => Ok(texture.clone()),
+                    None => Err(error_at(&token, &format!("unknown texture '{}'", name)))
+                }
+            }
+            _ => Ok(Box::new(SolidColor::new(self.expect_vec3()?)))
+        }
+    }
+
+    // Either the name of a texture or a single number in [min, max] used for all channels.
+    fn expect_scalar_texture(&mut self, min: f64, max: f64) -> Result<Box<dyn Texture>, SceneError> {
+        match &self.peek_token().kind {
+            TokenKind::Identifier(_) => self.expect_texture(),
+            _ => {
+                let number = self.expect_number_in(min, max)?;
+                Ok(Box::new(SolidColor::new(Color::new(number, number, number))))
+            }
+        }
+    }
+
+    fn expect_material(&mut self) -> Result<Box<dyn Material>, SceneError> {
+        let token = self.peek_token().clone();
+        let name = self.expect_identifier()?;
+        match self.materials.get(&name) {
+            Some(material) => Ok(material.clone()),
+            None => Err(error_at(&token, &format!("unknown material '{}'", name)))
+        }
+    }
+}
+
+fn error_at(token: &Token, message: &str) -> SceneError {
+    SceneError::new(token.line, token.column, message)
+}
+
+fn require<T>(statement: &Token, block_name: &str, key: &str, value: Option<T>) -> Result<T, SceneError> {
+    match value {
+        Some(value) => Ok(value),
+        None => Err(error_at(statement, &format!("{} is missing '{}'", block_name, key)))
+    }
+}
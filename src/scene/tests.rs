@@ -0,0 +1,87 @@
+
+// Scene files parsed from strings: what a valid scene produces, and errors that point at the line and
+// column of the offending token.
+
+use std::path::Path;
+
+use crate::scene::{Scene, SceneError, parse_scene};
+
+
+const MATERIAL: &str = "material white lambertian { albedo 0.8 0.8 0.8 }\n";
+
+
+fn parse(source: &str) -> Result<Scene, SceneError> {
+    parse_scene(Path::new("test.scene"), source)
+}
+
+fn assert_error_at(source: &str, line: usize, column: usize, message: &str) {
+    match parse(source) {
+        Ok(_) => panic!("'{}' was accepted", source),
+        Err(error) => {
+            assert_eq!((error.line, error.column), (line, column), "wrong position for '{}': {}", source, error);
+            assert!(error.message.contains(message), "'{}' gave '{}', expected '{}'", source, error.message, message);
+        }
+    }
+}
+
+#[test]
+fn valid_scene_is_read() {
+    let source = format!("# comment\nsettings {{ sample_count 8 bound_limit 4 }}\ncamera {{ look_from 0 1 5 look_to 0 1 0 fov 45 }}\n{}\
+        sphere {{ center 0 0 0 radius 1 material white }}\n\
+        sphere {{ center 0 0 0 radius -0.9 material white }}\n\
+        triangle {{ v0 0 0 0 v1 1 0 0 v2 0 1 0 material white }}\n", MATERIAL);
+    let scene = parse(&source).unwrap();
+    assert_eq!(scene.settings.sample_count, 8);
+    assert_eq!(scene.settings.bound_limit, 4);
+    assert_eq!(scene.camera.look_from.z, 5.0);
+    assert_eq!(scene.world.get_object_count(), 3);
+}
+
+#[test]
+fn lexer_errors_point_at_the_token() {
+    assert_error_at("settings {\n  sample_count 1.2.3 }", 2, 16, "invalid number '1.2.3'");
+    assert_error_at("mesh { file \"open", 1, 13, "unterminated string");
+    assert_error_at("camera { fov 20 } $", 1, 19, "unexpected character '$'");
+}
+
+#[test]
+fn syntax_errors_point_at_the_token() {
+    assert_error_at("\n\n   cube { }", 3, 4, "unknown statement 'cube'");
+    assert_error_at("camera fov 20", 1, 8, "expected '{' after 'camera'");
+    assert_error_at("camera {\n fov 20\n zoom 2 }", 3, 2, "unknown property 'zoom' in camera");
+    assert_error_at("camera { fov 20", 1, 8, "'camera' block is never closed");
+    assert_error_at("sphere { center 0 0 material white }", 1, 21, "expected a number, found");
+    assert_error_at("sphere { center 0 0 0 radius 1 material red }", 1, 41, "unknown material 'red'");
+    assert_error_at("\nsphere { center 0 0 0 radius 1 }", 2, 1, "sphere is missing 'material'");
+}
+
+#[test]
+fn out_of_range_values_point_at_the_value() {
+    assert_error_at("settings { sample_count 0 }", 1, 25, "whole number of at least 1");
+    assert_error_at("settings { sample_count 2.5 }", 1, 25, "whole number of at least 1");
+    assert_error_at("material m metal { fuzziness 1.5 }", 1, 30, "1.5 is out of range");
+    assert_error_at("material m dielectric { refraction_index 0 }", 1, 42, "0 is out of range");
+    assert_error_at(&format!("{}sphere {{ center 0 0 0 radius 0 material white }}", MATERIAL), 2, 30, "other than 0");
+}
+
+#[test]
+fn seeds_use_the_whole_u64_range() {
+    // 2^64 - 1 is not exactly representable as a f64
+    let scene = parse("settings { seed 18446744073709551615 }").unwrap();
+    assert_eq!(scene.settings.seed, u64::MAX);
+    let scene = parse("settings { seed 9007199254740993 }").unwrap();
+    assert_eq!(scene.settings.seed, 9007199254740993);
+
+    assert_error_at("settings { seed 18446744073709551616 }", 1, 17, "whole number between 0 and");
+    assert_error_at("settings { seed -1 }", 1, 17, "whole number between 0 and");
+    assert_error_at("settings { seed 1.5 }", 1, 17, "whole number between 0 and");
+}
+
+#[test]
+fn degenerate_cameras_are_rejected() {
+    assert_error_at("camera { fov 0 }", 1, 14, "between 0 and 180");
+    assert_error_at("camera { fov 180 }", 1, 14, "between 0 and 180");
+    assert_error_at("camera { focus_dist 0 }", 1, 21, "0 is out of range");
+    assert_error_at("\n  camera { look_from 1 2 3 look_to 1 2 3 }", 2, 3, "look_from and look_to are the same point");
+    assert!(parse("camera { fov 179.9 focus_dist 0.01 }").is_ok());
+}
@@ -0,0 +1,83 @@
+
+use crate::texture::Texture;
+use crate::texture::solid_color::SolidColor;
+use crate::math::vec3::{Vec3, Point3, Color};
+
+
+// Alternates between two textures in a grid over the uv square, scale is the number of cells per side.
+#[derive(Clone)]
+pub struct UvChecker {
+    odd: Box<dyn Texture>,
+    even: Box<dyn Texture>,
+    scale: f64
+}
+
+impl UvChecker {
+    pub fn new_default() -> UvChecker {
+        UvChecker::new(
+            Box::new(SolidColor::new(Color::new(0.0, 0.0, 0.0))),
+            Box::new(SolidColor::new(Color::new(1.0, 1.0, 1.0))),
+            10.0)
+    }
+
+    pub fn new(odd: Box<dyn Texture>, even: Box<dyn Texture>, scale: f64) -> UvChecker {
+        UvChecker { odd, even, scale }
+    }
+
+    fn get_cell_texture(&self, uv: (f64, f64)) -> &dyn Texture {
+        let cell = (uv.0 * self.scale).floor() as i64 + (uv.1 * self.scale).floor() as i64;
+        if cell % 2 == 0 { &*self.even } else { &*self.odd }
+    }
+}
+
+impl Texture for UvChecker {
+    fn value(&self, uv: (f64, f64), point: &Point3, normal: &Vec3) -> Color {
+        self.get_cell_texture(uv).value(uv, point, normal)
+    }
+
+    fn value_filtered(&self, uv: (f64, f64), point: &Point3, normal: &Vec3, uv_footprint: f64) -> Color {
+        self.get_cell_texture(uv).value_filtered(uv, point, normal, uv_footprint)
+    }
+}
+
+
+// Alternates between two textures in a grid of cubes in world space, scale is the size of a cube.
+// Works on objects without a usable uv mapping.
+#[derive(Clone)]
+pub struct SpatialChecker {
+    odd: Box<dyn Texture>,
+    even: Box<dyn Texture>,
+    scale: f64
+}
+
+impl SpatialChecker {
+    pub fn new_default() -> SpatialChecker {
+        SpatialChecker::new(
+            Box::new(SolidColor::new(Color::new(0.0, 0.0, 0.0))),
+            Box::new(SolidColor::new(Color::new(1.0, 1.0, 1.0))),
+            1.0)
+    }
+
+    pub fn new(odd: Box<dyn Texture>, even: Box<dyn Texture>, scale: f64) -> SpatialChecker {
+        SpatialChecker { odd, even, scale }
+    }
+
+    fn get_cell_texture(&self, point: &Point3) -> &dyn Texture {
+        let inv_scale = 1.0 / self.scale;
+        let cell =
+            (point.x * inv_scale).floor() as i64 +
+            (point.y * inv_scale).floor() as i64 +
+            (point.z * inv_scale).floor() as i64;
+        if cell % 2 == 0 { &*self.even } else { &*self.odd }
+    }
+}
+
+impl Texture for SpatialChecker {
+    fn value(&self, uv: (f64, f64), point: &Point3, normal: &Vec3) -> Color {
+        self.get_cell_texture(point).value(uv, point, normal)
+    }
+
+    fn value_filtered(&self, uv: (f64, f64), point: &Point3, normal: &Vec3, uv_footprint: f64) -> Color {
+        self.get_cell_texture(point).value_filtered(uv, point, normal, uv_footprint)
+    }
+}
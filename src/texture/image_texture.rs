@@ -0,0 +1,219 @@
+
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::import::ImportError;
+use crate::texture::Texture;
+use crate::math::vec3::{Vec3, Point3, Color};
+
+
+// How uv coordinates outside of [0, 1] are folded back onto the image.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum WrapMode {
+    Repeat,
+    Clamp,
+    Mirror
+}
+
+// Nearest always reads the full resolution image, Bilinear blends the mip level closest
+// to the footprint and Trilinear also blends between the two nearest levels.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum FilterMode {
+    Nearest,
+    Bilinear,
+    Trilinear
+}
+
+struct MipLevel {
+    width: usize,
+    height: usize,
+    pixels: Vec<Color>
+}
+
+impl MipLevel {
+    // 2x2 box filter, odd sizes fold their last row or column into the previous texel
+    fn downsample(&self) -> MipLevel {
+        let width = (self.width / 2).max(1);
+        let height = (self.height / 2).max(1);
+        let mut pixels = Vec::with_capacity(width * height);
+        for y in 0 .. height {
+            for x in 0 .. width {
+                let x0 = (x * 2).min(self.width - 1);
+                let x1 = (x * 2 + 1).min(self.width - 1);
+                let y0 = (y * 2).min(self.height - 1);
+                let y1 = (y * 2 + 1).min(self.height - 1);
+                let sum =
+                    self.pixels[y0 * self.width + x0] + self.pixels[y0 * self.width + x1] +
+                    self.pixels[y1 * self.width + x0] + self.pixels[y1 * self.width + x1];
+                pixels.push(sum * 0.25);
+            }
+        }
+
+        MipLevel { width, height, pixels }
+    }
+}
+
+// Bitmap with a pre-built mip chain, v = 0 is the bottom row.
+#[derive(Clone)]
+pub struct ImageTexture {
+    levels: Arc<Vec<MipLevel>>,
+    wrap_mode: WrapMode,
+    filter_mode: FilterMode
+}
+
+impl ImageTexture {
+    // pixels are linear colors, row by row from the top
+    pub fn new(width: usize, height: usize, pixels: Vec<Color>) -> ImageTexture {
+        if width == 0 || height == 0 || pixels.len() != width * height {
+            panic!("wrong image texture size!");
+        }
+
+        let mut levels = vec![MipLevel { width, height, pixels }];
+        loop {
+            let last = &levels[levels.len() - 1];
+            if last.width == 1 && last.height == 1 {
+                break;
+            }
+            let next = last.downsample();
+            levels.push(next);
+        }
+
+        ImageTexture {
+            levels: Arc::new(levels),
+            wrap_mode: WrapMode::Repeat,
+            filter_mode: FilterMode::Trilinear
+        }
+    }
+
+    // Anything the image crate can decode with the enabled features, i.e. PNG, JPEG and PPM.
+    // Color images are usually sRGB encoded, data such as roughness maps are not.
+    pub fn load(path: &Path, is_srgb: bool) -> Result<ImageTexture, ImportError> {
+        let decoded = match image::open(path) {
+            Ok(decoded) => decoded.to_rgb8(),
+            Err(image::ImageError::IoError(error)) => {
+                return Err(ImportError::Io { path: path.to_path_buf(), error });
+            }
+            Err(error) => {
+                return Err(ImportError::Image { path: path.to_path_buf(), message: error.to_string() });
+            }
+        };
+
+        let (width, height) = (decoded.width() as usize, decoded.height() as usize);
+        if width == 0 || height == 0 {
+            return Err(ImportError::Image { path: path.to_path_buf(), message: "image is empty".to_string() });
+        }
+
+        let mut decode_table = [0.0; 256];
+        for (value, decoded_value) in decode_table.iter_mut().enumerate() {
+            let encoded = value as f64 / 255.0;
+            *decoded_value = if is_srgb { srgb_to_linear(encoded) } else { encoded };
+        }
+
+        let pixels = decoded.pixels()
+            .map(|pixel| Color::new(decode_table[pixel[0] as usize], decode_table[pixel[1] as usize], decode_table[pixel[2] as usize]))
+            .collect();
+        Ok(ImageTexture::new(width, height, pixels))
+    }
+
+    pub fn set_wrap_mode(&mut self, wrap_mode: WrapMode) {
+        self.wrap_mode = wrap_mode;
+    }
+
+    pub fn set_filter_mode(&mut self, filter_mode: FilterMode) {
+        self.filter_mode = filter_mode;
+    }
+
+    pub fn get_size(&self) -> (usize, usize) {
+        (self.levels[0].width, self.levels[0].height)
+    }
+
+    pub fn get_level_count(&self) -> usize {
+        self.levels.len()
+    }
+
+    fn wrap(&self, coordinate: i64, size: usize) -> usize {
+        let size = size as i64;
+        let wrapped = match self.wrap_mode {
+            WrapMode::Repeat => coordinate.rem_euclid(size),
+            WrapMode::Clamp => coordinate.clamp(0, size - 1),
+            WrapMode::Mirror => {
+                let folded = coordinate.rem_euclid(size * 2);
+                if folded >= size { size * 2 - 1 - folded } else { folded }
+            }
+        };
+        wrapped as usize
+    }
+
+    fn get_texel(&self, level: &MipLevel, x: i64, y: i64) -> Color {
+        let x = self.wrap(x, level.width);
+        let y = self.wrap(y, level.height);
+        level.pixels[y * level.width + x]
+    }
+
+    fn sample_nearest(&self, level: &MipLevel, uv: (f64, f64)) -> Color {
+        let x = (uv.0 * level.width as f64).floor() as i64;
+        let y = ((1.0 - uv.1) * level.height as f64).floor() as i64;
+        self.get_texel(level, x, y)
+    }
+
+    fn sample_bilinear(&self, level: &MipLevel, uv: (f64, f64)) -> Color {
+        // texel centers sit at half coordinates
+        let x = uv.0 * level.width as f64 - 0.5;
+        let y = (1.0 - uv.1) * level.height as f64 - 0.5;
+        let x0 = x.floor();
+        let y0 = y.floor();
+        let tx = x - x0;
+        let ty = y - y0;
+        let (x0, y0) = (x0 as i64, y0 as i64);
+
+        let top = self.get_texel(level, x0, y0) * (1.0 - tx) + self.get_texel(level, x0 + 1, y0) * tx;
+        let bottom = self.get_texel(level, x0, y0 + 1) * (1.0 - tx) + self.get_texel(level, x0 + 1, y0 + 1) * tx;
+        top * (1.0 - ty) + bottom * ty
+    }
+
+    // fractional mip level whose texels are about as large as the footprint
+    fn get_lod(&self, uv_footprint: f64) -> f64 {
+        let texel_footprint = uv_footprint * self.levels[0].width.max(self.levels[0].height) as f64;
+        if texel_footprint <= 1.0 {
+            return 0.0;
+        }
+
+        texel_footprint.log2().min((self.levels.len() - 1) as f64)
+    }
+}
+
+impl Texture for ImageTexture {
+    fn value(&self, uv: (f64, f64), point: &Point3, normal: &Vec3) -> Color {
+        self.value_filtered(uv, point, normal, 0.0)
+    }
+
+    fn value_filtered(&self, uv: (f64, f64), point: &Point3, normal: &Vec3, uv_footprint: f64) -> Color {
+        match self.filter_mode {
+            FilterMode::Nearest => self.sample_nearest(&self.levels[0], uv),
+            FilterMode::Bilinear => {
+                let level = self.get_lod(uv_footprint).round() as usize;
+                self.sample_bilinear(&self.levels[level], uv)
+            }
+            FilterMode::Trilinear => {
+                let lod = self.get_lod(uv_footprint);
+                let fine_level = lod.floor() as usize;
+                let coarse_level = (fine_level + 1).min(self.levels.len() - 1);
+                let blend = lod - fine_level as f64;
+
+                let fine = self.sample_bilinear(&self.levels[fine_level], uv);
+                if blend <= 0.0 || coarse_level == fine_level {
+                    return fine;
+                }
+                fine * (1.0 - blend) + self.sample_bilinear(&self.levels[coarse_level], uv) * blend
+            }
+        }
+    }
+}
+
+pub fn srgb_to_linear(value: f64) -> f64 {
+    if value <= 0.04045 {
+        value / 12.92
+    } else {
+        ((value + 0.055) / 1.055).powf(2.4)
+    }
+}
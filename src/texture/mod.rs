@@ -0,0 +1,27 @@
+pub mod solid_color;
+pub mod checker;
+pub mod image_texture;
+pub mod noise;
+
+use dyn_clone::DynClone;
+
+use crate::math::vec3::{Vec3, Point3, Color};
+use crate::object::HitRecord;
+
+
+// Color lookup for a surface point, uv is the surface parameterization of the hit object.
+pub trait Texture: Send + Sync + DynClone {
+    fn value(&self, uv: (f64, f64), point: &Point3, normal: &Vec3) -> Color;
+
+    // uv_footprint is the size of the area being shaded in uv units,
+    // textures with fine detail average over it instead of aliasing.
+    fn value_filtered(&self, uv: (f64, f64), point: &Point3, normal: &Vec3, uv_footprint: f64) -> Color {
+        self.value(uv, point, normal)
+    }
+}
+
+dyn_clone::clone_trait_object!(Texture);
+
+pub fn sample_texture(texture: &dyn Texture, hit_record: &HitRecord) -> Color {
+    texture.value_filtered(hit_record.uv, &hit_record.point, &hit_record.normal, hit_record.uv_footprint)
+}
@@ -0,0 +1,156 @@
+
+use crate::texture::Texture;
+use crate::texture::solid_color::SolidColor;
+use crate::math::noise::{Perlin, Worley};
+use crate::math::vec3::{Vec3, Point3, Color};
+
+
+// All noise textures blend from low to high with a pattern evaluated at the world space point,
+// scale is the pattern frequency and the seed picks one of many equally looking patterns.
+
+fn blend(low: &dyn Texture, high: &dyn Texture, t: f64, uv: (f64, f64), point: &Point3, normal: &Vec3, uv_footprint: f64) -> Color {
+    let t = t.clamp(0.0, 1.0);
+    low.value_filtered(uv, point, normal, uv_footprint) * (1.0 - t) + high.value_filtered(uv, point, normal, uv_footprint) * t
+}
+
+fn new_black() -> Box<dyn Texture> {
+    Box::new(SolidColor::new(Color::new(0.0, 0.0, 0.0)))
+}
+
+fn new_white() -> Box<dyn Texture> {
+    Box::new(SolidColor::new(Color::new(1.0, 1.0, 1.0)))
+}
+
+
+// Plain fractal Perlin noise.
+#[derive(Clone)]
+pub struct NoiseTexture {
+    perlin: Perlin,
+    low: Box<dyn Texture>,
+    high: Box<dyn Texture>,
+    scale: f64,
+    octaves: u32
+}
+
+impl NoiseTexture {
+    pub fn new_default() -> NoiseTexture {
+        NoiseTexture::new(new_black(), new_white(), 1.0, 1, 0)
+    }
+
+    pub fn new(low: Box<dyn Texture>, high: Box<dyn Texture>, scale: f64, octaves: u32, seed: u64) -> NoiseTexture {
+        NoiseTexture { perlin: Perlin::new(seed), low, high, scale, octaves }
+    }
+}
+
+impl Texture for NoiseTexture {
+    fn value(&self, uv: (f64, f64), point: &Point3, normal: &Vec3) -> Color {
+        self.value_filtered(uv, point, normal, 0.0)
+    }
+
+    fn value_filtered(&self, uv: (f64, f64), point: &Point3, normal: &Vec3, uv_footprint: f64) -> Color {
+        let t = 0.5 * (1.0 + self.perlin.fbm(&(*point * self.scale), self.octaves));
+        blend(&*self.low, &*self.high, t, uv, point, normal, uv_footprint)
+    }
+}
+
+
+// Sine bands along z, bent by turbulence into veins.
+#[derive(Clone)]
+pub struct MarbleTexture {
+    perlin: Perlin,
+    low: Box<dyn Texture>,
+    high: Box<dyn Texture>,
+    scale: f64,
+    octaves: u32
+}
+
+impl MarbleTexture {
+    pub fn new_default() -> MarbleTexture {
+        MarbleTexture::new(new_black(), new_white(), 1.0, 7, 0)
+    }
+
+    pub fn new(low: Box<dyn Texture>, high: Box<dyn Texture>, scale: f64, octaves: u32, seed: u64) -> MarbleTexture {
+        MarbleTexture { perlin: Perlin::new(seed), low, high, scale, octaves }
+    }
+}
+
+impl Texture for MarbleTexture {
+    fn value(&self, uv: (f64, f64), point: &Point3, normal: &Vec3) -> Color {
+        self.value_filtered(uv, point, normal, 0.0)
+    }
+
+    fn value_filtered(&self, uv: (f64, f64), point: &Point3, normal: &Vec3, uv_footprint: f64) -> Color {
+        let scaled_point = *point * self.scale;
+        let turbulence = self.perlin.turbulence(&scaled_point, self.octaves);
+        let t = 0.5 * (1.0 + (scaled_point.z + 10.0 * turbulence).sin());
+        blend(&*self.low, &*self.high, t, uv, point, normal, uv_footprint)
+    }
+}
+
+
+// Growth rings around the y axis, wobbled by noise.
+#[derive(Clone)]
+pub struct WoodTexture {
+    perlin: Perlin,
+    low: Box<dyn Texture>,
+    high: Box<dyn Texture>,
+    scale: f64,
+    octaves: u32
+}
+
+impl WoodTexture {
+    pub fn new_default() -> WoodTexture {
+        WoodTexture::new(new_black(), new_white(), 1.0, 3, 0)
+    }
+
+    pub fn new(low: Box<dyn Texture>, high: Box<dyn Texture>, scale: f64, octaves: u32, seed: u64) -> WoodTexture {
+        WoodTexture { perlin: Perlin::new(seed), low, high, scale, octaves }
+    }
+}
+
+impl Texture for WoodTexture {
+    fn value(&self, uv: (f64, f64), point: &Point3, normal: &Vec3) -> Color {
+        self.value_filtered(uv, point, normal, 0.0)
+    }
+
+    fn value_filtered(&self, uv: (f64, f64), point: &Point3, normal: &Vec3, uv_footprint: f64) -> Color {
+        let scaled_point = *point * self.scale;
+        let radius = (scaled_point.x * scaled_point.x + scaled_point.z * scaled_point.z).sqrt();
+        let rings = radius + self.perlin.fbm(&scaled_point, self.octaves);
+        let t = rings - rings.floor();
+        blend(&*self.low, &*self.high, t, uv, point, normal, uv_footprint)
+    }
+}
+
+
+// Cells around random feature points, low in the middle of a cell and high on its borders.
+#[derive(Clone)]
+pub struct CellularTexture {
+    worley: Worley,
+    low: Box<dyn Texture>,
+    high: Box<dyn Texture>,
+    scale: f64
+}
+
+impl CellularTexture {
+    pub fn new_default() -> CellularTexture {
+        CellularTexture::new(new_black(), new_white(), 1.0, 0)
+    }
+
+    pub fn new(low: Box<dyn Texture>, high: Box<dyn Texture>, scale: f64, seed: u64) -> CellularTexture {
+        CellularTexture { worley: Worley::new(seed), low, high, scale }
+    }
+}
+
+impl Texture for CellularTexture {
+    fn value(&self, uv: (f64, f64), point: &Point3, normal: &Vec3) -> Color {
+        self.value_filtered(uv, point, normal, 0.0)
+    }
+
+    fn value_filtered(&self, uv: (f64, f64), point: &Point3, normal: &Vec3, uv_footprint: f64) -> Color {
+        let (closest, second_closest) = self.worley.distances(&(*point * self.scale));
+        // zero where two cells meet
+        let t = 1.0 - (second_closest - closest).min(1.0);
+        blend(&*self.low, &*self.high, t, uv, point, normal, uv_footprint)
+    }
+}
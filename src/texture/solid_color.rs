@@ -0,0 +1,25 @@
+
+use crate::texture::Texture;
+use crate::math::vec3::{Vec3, Point3, Color};
+
+
+#[derive(Clone)]
+pub struct SolidColor {
+    color: Color
+}
+
+impl SolidColor {
+    pub fn new_default() -> SolidColor {
+        SolidColor { color: Color::new(0.0, 0.0, 0.0) }
+    }
+
+    pub fn new(color: Color) -> SolidColor {
+        SolidColor { color }
+    }
+}
+
+impl Texture for SolidColor {
+    fn value(&self, uv: (f64, f64), point: &Point3, normal: &Vec3) -> Color {
+        self.color
+    }
+}
@@ -1,11 +1,16 @@
 
 pub mod ray_worker;
+pub mod tile;
+pub mod render_control;
 
 use std::thread::{JoinHandle, self};
-use std::sync::mpsc::Sender;
+use std::sync::Arc;
 use crate::camera::Camera;
 use crate::world::World;
-use self::ray_worker::{RayWorker, RayResult, RayWorkerSettings};
+use crate::film::Film;
+use self::ray_worker::{RayWorker, RayWorkerSettings};
+use self::tile::TileQueue;
+use self::render_control::RenderControl;
 
 pub struct RayWorkerManager {
     thread_handles: Vec<JoinHandle<()>>,
@@ -20,12 +25,16 @@ impl RayWorkerManager {
         }
     }
 
-    pub fn start_worker(&mut self, world: World, camera: Camera, pixel_sender: Sender<RayResult>, worker_settings: RayWorkerSettings) {
+    pub fn start_worker(&mut self, world: Arc<World>, camera: Camera, tile_queue: Arc<TileQueue>, control: Arc<RenderControl>, film: Arc<Film>, worker_settings: RayWorkerSettings) {
+        let id = self.worker_nums;
         let thread_handle = thread::spawn(move || {
             let mut ray_worker = RayWorker::new(
+                    id,
                     world,  
                     camera,
-                    pixel_sender, 
+                    tile_queue,
+                    control,
+                    film,
                     worker_settings
                 );
             ray_worker.run();
@@ -36,14 +45,9 @@ impl RayWorkerManager {
     }
 
     pub fn join_workers(&mut self) {
-        while !self.thread_handles.is_empty() {
-            let popped_handle = self.thread_handles.pop();
-            match popped_handle {
-                Some(handle) => {
-                    handle.join().unwrap();
-                }
-                _ => { }
-            }
+        // a worker that panicked has already failed the render through its RenderControl
+        while let Some(handle) = self.thread_handles.pop() {
+            let _ = handle.join();
         }
 
         self.worker_nums = 0;
@@ -3,114 +3,128 @@ use crate::camera::Camera;
 use crate::world::World;
 use crate::math::vec3::Color;
 use crate::ray::Ray;
-use std::sync::mpsc::Sender;
-use rand::Rng;
+use crate::object::HitRecord;
+use crate::film::{Film, FilmSample};
+use crate::threading::tile::TileQueue;
+use crate::threading::render_control::RenderControl;
+use crate::sampler::{Sampler, SamplerType};
+use std::sync::Arc;
+use std::thread;
+
+
+const SHADOW_EPSILON: f64 = 1e-4;
+
+// Veach's power heuristic with beta = 2 for combining two sampling strategies.
+fn power_heuristic(pdf: f64, other_pdf: f64) -> f64 {
+    let squared = pdf * pdf;
+    let other_squared = other_pdf * other_pdf;
+    if squared + other_squared <= 0.0 {
+        return 0.0;
+    }
+
+    squared / (squared + other_squared)
+}
+
+
+// Dropped when the worker exits, on a panic it fails the render. The job the worker was on would never
+// finish, so the render thread would wait for it forever and so would workers blocked on the next pass.
+struct PanicGuard {
+    control: Arc<RenderControl>,
+    tile_queue: Arc<TileQueue>
+}
+
+impl Drop for PanicGuard {
+    fn drop(&mut self) {
+        if thread::panicking() {
+            self.control.fail();
+            self.tile_queue.abort();
+        }
+    }
+}
 
 
 #[derive(Clone, Copy)]
 pub struct RayWorkerSettings {
     pub screen_size: (usize, usize),
-    pub bound_y: (usize, usize),
-    pub sample_count: u32,
     pub bound_limit: u32,
-}
-
-#[derive(Clone)]
-pub struct RayResult {
-   pub srgb_buffer: Vec<Color>,
-   pub bound_y: (usize, usize) 
+    pub adaptive_threshold: f64,
+    pub min_sample_count: u32,
+    pub sampler: SamplerType,
+    pub sample_count: u32,
+    pub seed: u64
 }
 
 pub struct RayWorker {
-    world: World,
+    id: usize,
+    world: Arc<World>,
     camera: Camera,
-    accumulated_buffer: Vec<Color>,
-    srgb_buffer: Vec<Color>,
-    buffer_sender: Sender<RayResult>,
+    tile_queue: Arc<TileQueue>,
+    control: Arc<RenderControl>,
+    film: Arc<Film>,
     settings: RayWorkerSettings
 }
 
 impl RayWorker {
-    pub fn new(world: World, camera: Camera, pixel_sender: Sender<RayResult>, settings: RayWorkerSettings) -> RayWorker {
-        if settings.bound_y.0 > settings.bound_y.1 {
-            panic!("wrong ray worker settings!");
-        }
-
-        let y_size = settings.bound_y.1 - settings.bound_y.0;
-        let buffer_size = settings.screen_size.0 * y_size;
-
-        let mut accumulated_buffer: Vec<Color> = Vec::new();
-        accumulated_buffer.resize(buffer_size, Color::new_default());
-
-        let mut srgb_buffer: Vec<Color> = Vec::new();
-        srgb_buffer.resize(buffer_size, Color::new_default());
-
+    pub fn new(id: usize, world: Arc<World>, camera: Camera, tile_queue: Arc<TileQueue>, control: Arc<RenderControl>, film: Arc<Film>, settings: RayWorkerSettings) -> RayWorker {
         RayWorker { 
+            id,
             world, 
             camera, 
-            accumulated_buffer,
-            srgb_buffer,
-            buffer_sender: pixel_sender, 
+            tile_queue,
+            control,
+            film,
             settings 
         }
     }
 
     pub fn run(&mut self) {
-        println!("start ray worker (id: {id})", id = self.settings.bound_y.0);
-
-        let start_y = self.settings.bound_y.0;
-        let end_y = self.settings.bound_y.1;
-        for sample_count in 0 .. self.settings.sample_count {
+        println!("start ray worker (id: {id})", id = self.id);
 
-            for y in start_y .. end_y {
-                for x in 0 .. self.settings.screen_size.0 {
-                    let final_color = self.sample_ray((x, y));
-                    self.apply_color((x, y), &final_color, sample_count + 1);
-                }
-            }
+        let _panic_guard = PanicGuard { control: Arc::clone(&self.control), tile_queue: Arc::clone(&self.tile_queue) };
+        let mut sampler = self.settings.sampler.create(self.settings.sample_count, self.settings.seed);
 
-            // send srgb_buffer
-            let send_result = RayResult {
-                srgb_buffer: self.srgb_buffer.clone(),
-                bound_y: self.settings.bound_y
+        while self.control.wait_if_paused() {
+            let job = match self.tile_queue.next_job() {
+                Some(job) => job,
+                None => break
             };
-            self.buffer_sender.send(send_result).unwrap();
-        }
+            let (tile, pass) = (job.tile, job.pass);
 
-        println!("ended ray worker (id: {id})", id = self.settings.bound_y.0);
-    }
+            // with adaptive sampling, pixels whose error is low enough are skipped after the first passes
+            let converged_pixels = match self.settings.adaptive_threshold > 0.0 && pass >= self.settings.min_sample_count {
+                true => self.film.get_converged_pixels(&tile, self.settings.adaptive_threshold, self.settings.min_sample_count),
+                false => vec![false; tile.get_pixel_count()]
+            };
 
-    fn apply_color(&mut self, position: (usize, usize), color: &Color, sample_count: u32) {
-        if sample_count <= 0 {
-            panic!("wrong sample count param!");
-        }
+            let mut samples: Vec<Option<FilmSample>> = Vec::with_capacity(tile.get_pixel_count());
+            for y in tile.y .. tile.y + tile.height {
+                for x in tile.x .. tile.x + tile.width {
+                    samples.push(match converged_pixels[samples.len()] {
+                        true => None,
+                        false => Some(self.sample_ray((x, y), pass, &mut *sampler))
+                    });
+                }
+            }
 
-        // set buffer
-        let offseted_y = position.1 - self.settings.bound_y.0;
-        let index: usize = (self.settings.screen_size.0 * offseted_y + position.0) as usize;
-        self.accumulated_buffer[index] += *color;
-
-        // send gamma corrected color
-        let mut corrected_color = self.accumulated_buffer[index];
-        let scale = 1.0 / sample_count as f64;
-        for i in 0 .. 3 {
-            let mut channel = corrected_color[i];
-            channel *= scale;
-            channel = channel.sqrt();
-            channel = channel.clamp(0.0, 1.0);
-            corrected_color.set_from_index(i, channel);
+            self.film.add_tile(&tile, &samples);
+            self.tile_queue.finish_job(&job);
         }
 
-        self.srgb_buffer[index] = corrected_color;
+        println!("ended ray worker (id: {id})", id = self.id);
     }
 
-    fn ray_color(&self, ray: &Ray) -> Color {
-        self.reflect_ray_recursive(ray, self.settings.bound_limit)
+    fn ray_color(&self, ray: &Ray, sampler: &mut dyn Sampler) -> FilmSample {
+        let mut sample = FilmSample::new_background(self.world.get_sky_color());
+        sample.color = self.reflect_ray_recursive(ray, self.settings.bound_limit, None, Some(&mut sample), sampler);
+        sample
     }
 
-    fn reflect_ray_recursive(&self, ray: &Ray, bound_count: u32) -> Color {
+    // bsdf_pdf is the pdf of the bounce that spawned ray, None for camera rays and specular bounces
+    // which could not have been found by light sampling.
+    // first_hit is only given for camera rays, it receives the auxiliary passes of the hit.
+    fn reflect_ray_recursive(&self, ray: &Ray, bound_count: u32, bsdf_pdf: Option<f64>, first_hit: Option<&mut FilmSample>, sampler: &mut dyn Sampler) -> Color {
         if bound_count == 0 {
-            return self.world.get_sky_color();
+            return Color::new_default();
         }
 
         let hit_record = self.world.world_hit(ray, 0.0001, f64::MAX);
@@ -118,13 +132,40 @@ impl RayWorker {
         let out_color: Color;
         match hit_record {
             Ok(record) => {
-                let materal_result = record.material.scatter(ray, &record);
+                if let Some(sample) = first_hit {
+                    sample.depth = record.weight * ray.get_direction().length();
+                    sample.normal = record.normal;
+                    sample.albedo = record.material.albedo(&record);
+                    sample.position = record.point;
+                    sample.material_id = record.material.get_id();
+                    sample.object_id = record.object_id;
+                }
+
+                let mut emitted_color = record.material.emitted(&record);
+                if let Some(bsdf_pdf) = bsdf_pdf {
+                    if !emitted_color.is_near_zero() {
+                        let light_pdf = self.world.light_pdf(ray.get_origin(), ray.get_direction());
+                        emitted_color *= power_heuristic(bsdf_pdf, light_pdf);
+                    }
+                }
+
+                let materal_result = record.material.scatter(ray, &record, sampler);
                 match materal_result {
-                    Some(result) => {
-                        out_color = result.attenuation * self.reflect_ray_recursive(&result.scattered_ray, bound_count - 1);
+                    Some(mut result) => {
+                        // the cone keeps widening from where it hit, so reflections get blurrier texture detail
+                        result.scattered_ray.set_cone(ray.get_cone_width(record.weight), ray.get_cone_spread());
+
+                        let (direct_color, next_bsdf_pdf) = if result.is_specular {
+                            (Color::new_default(), None)
+                        } else {
+                            (self.sample_direct_light(ray, &record, sampler), Some(result.pdf))
+                        };
+
+                        let indirect_color = result.attenuation * self.reflect_ray_recursive(&result.scattered_ray, bound_count - 1, next_bsdf_pdf, None, sampler);
+                        out_color = emitted_color + direct_color + indirect_color;
                     }
                     _ => { 
-                        out_color = self.world.get_sky_color();
+                        out_color = emitted_color;
                     }
                 }
             }
@@ -136,14 +177,52 @@ impl RayWorker {
         out_color
     }
 
-    fn sample_ray(&self, screen_pos: (usize, usize)) -> Color {
-        let u_rand = rand::thread_rng().gen_range(0.0 .. 1.0);
-        let u = (screen_pos.0 as f64 + u_rand) / (self.settings.screen_size.0 - 1) as f64;
-        let v_rand = rand::thread_rng().gen_range(0.0 .. 1.0);
-        let v = (screen_pos.1 as f64 + v_rand) / (self.settings.screen_size.1 - 1) as f64;
+    // Next event estimation: connects the hit point to a point on a light, weighted against bsdf sampling.
+    fn sample_direct_light(&self, ray: &Ray, record: &HitRecord, sampler: &mut dyn Sampler) -> Color {
+        let light_sample = match self.world.sample_light(&record.point, sampler) {
+            Some(light_sample) => light_sample,
+            None => return Color::new_default()
+        };
+
+        let to_light = light_sample.point - record.point;
+        let distance = to_light.length();
+        if distance <= 0.0 || light_sample.pdf <= 0.0 {
+            return Color::new_default();
+        }
+
+        let direction = to_light / distance;
+        let bsdf = record.material.evaluate(ray, record, &direction);
+        if bsdf.is_near_zero() {
+            return Color::new_default();
+        }
+
+        // the light is visible when the first thing the shadow ray hits is the sampled point itself
+        let shadow_ray = Ray::new(record.point, direction);
+        let light_record = match self.world.world_hit(&shadow_ray, 0.0001, distance * (1.0 + SHADOW_EPSILON)) {
+            Ok(light_record) => light_record,
+            Err(()) => return Color::new_default()
+        };
+
+        if light_record.weight < distance * (1.0 - SHADOW_EPSILON) {
+            return Color::new_default();
+        }
+
+        let emitted_color = light_record.material.emitted(&light_record);
+        let bsdf_pdf = record.material.scattering_pdf(ray, record, &direction);
+        let weight = power_heuristic(light_sample.pdf, bsdf_pdf);
+        bsdf * emitted_color * (weight / light_sample.pdf)
+    }
+
+    // sample_index is the pass, every pass takes one sample of each pixel
+    fn sample_ray(&self, screen_pos: (usize, usize), sample_index: u32, sampler: &mut dyn Sampler) -> FilmSample {
+        sampler.start_sample(screen_pos, sample_index);
+
+        let pixel_offset = sampler.get_2d();
+        let u = (screen_pos.0 as f64 + pixel_offset.0) / (self.settings.screen_size.0 - 1) as f64;
+        let v = (screen_pos.1 as f64 + pixel_offset.1) / (self.settings.screen_size.1 - 1) as f64;
 
-        let ray = self.camera.get_ray(u, v);
-        self.ray_color(&ray)
+        let ray = self.camera.get_ray(u, v, sampler);
+        self.ray_color(&ray, sampler)
     }
 
 }
@@ -0,0 +1,57 @@
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::Duration;
+
+
+// Signals from the render thread to its workers, checked between two tiles.
+pub struct RenderControl {
+    is_cancelled: AtomicBool,
+    is_paused: AtomicBool,
+    is_failed: AtomicBool
+}
+
+impl RenderControl {
+    pub fn new() -> RenderControl {
+        RenderControl {
+            is_cancelled: AtomicBool::new(false),
+            is_paused: AtomicBool::new(false),
+            is_failed: AtomicBool::new(false)
+        }
+    }
+
+    pub fn cancel(&self) {
+        self.is_cancelled.store(true, Ordering::Relaxed);
+    }
+
+    // a worker died, the others stop like on a cancel and the render can not finish
+    pub fn fail(&self) {
+        self.is_failed.store(true, Ordering::Relaxed);
+        self.cancel();
+    }
+
+    pub fn set_paused(&self, is_paused: bool) {
+        self.is_paused.store(is_paused, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.is_cancelled.load(Ordering::Relaxed)
+    }
+
+    pub fn is_failed(&self) -> bool {
+        self.is_failed.load(Ordering::Relaxed)
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.is_paused.load(Ordering::Relaxed)
+    }
+
+    // Blocks while paused, returns false when the render was cancelled and the worker should stop.
+    pub fn wait_if_paused(&self) -> bool {
+        while self.is_paused() && !self.is_cancelled() {
+            thread::sleep(Duration::from_millis(10));
+        }
+
+        !self.is_cancelled()
+    }
+}
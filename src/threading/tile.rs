@@ -0,0 +1,193 @@
+
+use std::sync::{Mutex, Condvar};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+
+// Rectangle of pixels rendered as one piece of work, y counts up from the bottom row.
+#[derive(Clone, Copy)]
+pub struct Tile {
+    pub x: usize,
+    pub y: usize,
+    pub width: usize,
+    pub height: usize
+}
+
+impl Tile {
+    pub fn get_pixel_count(&self) -> usize {
+        self.width * self.height
+    }
+}
+
+// Order in which tiles are handed out, the first tiles of every pass show up first.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum TileOrder {
+    // rows from the bottom of the image
+    Scanline,
+    // rings around the center of the image
+    Spiral,
+    // along a Hilbert curve starting at the center tile, neighbouring tiles are rendered close in time
+    Hilbert
+}
+
+// Splits the screen into tiles of at most tile_size pixels per side, sorted by order.
+pub fn make_tiles(screen_size: (usize, usize), tile_size: usize, order: TileOrder) -> Vec<Tile> {
+    let tile_size = tile_size.max(1);
+    let tiles_x = screen_size.0.div_ceil(tile_size);
+    let tiles_y = screen_size.1.div_ceil(tile_size);
+
+    let mut cells: Vec<(usize, usize)> = Vec::with_capacity(tiles_x * tiles_y);
+    for cell_y in 0 .. tiles_y {
+        for cell_x in 0 .. tiles_x {
+            cells.push((cell_x, cell_y));
+        }
+    }
+
+    // distances and angles are measured in tile units from the middle of the grid
+    let center = (tiles_x as f64 * 0.5, tiles_y as f64 * 0.5);
+    let offset_from_center = |cell: &(usize, usize)| (cell.0 as f64 + 0.5 - center.0, cell.1 as f64 + 0.5 - center.1);
+
+    match order {
+        TileOrder::Scanline => { }
+        TileOrder::Spiral => {
+            let get_key = |cell: &(usize, usize)| {
+                let (dx, dy) = offset_from_center(cell);
+                let ring = dx.abs().max(dy.abs()).floor();
+                (ring, dy.atan2(dx))
+            };
+            cells.sort_by(|lhs, rhs| get_key(lhs).partial_cmp(&get_key(rhs)).unwrap());
+        }
+        TileOrder::Hilbert => {
+            let mut grid_size = 1;
+            while grid_size < tiles_x.max(tiles_y) {
+                grid_size *= 2;
+            }
+            cells.sort_by_key(|cell| get_hilbert_index(grid_size, cell.0, cell.1));
+
+            // the curve is continuous, so starting in the middle only adds one jump back to its beginning
+            let get_distance = |cell: &(usize, usize)| {
+                let (dx, dy) = offset_from_center(cell);
+                dx * dx + dy * dy
+            };
+            let mut center_index = 0;
+            for (index, cell) in cells.iter().enumerate() {
+                if get_distance(cell) < get_distance(&cells[center_index]) {
+                    center_index = index;
+                }
+            }
+            cells.rotate_left(center_index);
+        }
+    }
+
+    cells.iter().map(|cell| {
+        let x = cell.0 * tile_size;
+        let y = cell.1 * tile_size;
+        Tile {
+            x,
+            y,
+            width: tile_size.min(screen_size.0 - x),
+            height: tile_size.min(screen_size.1 - y)
+        }
+    }).collect()
+}
+
+// Position of (x, y) along the Hilbert curve filling a grid_size x grid_size grid, grid_size is a power of two.
+fn get_hilbert_index(grid_size: usize, x: usize, y: usize) -> usize {
+    let (mut x, mut y) = (x, y);
+    let mut index = 0;
+    let mut step = grid_size / 2;
+    while step > 0 {
+        let region_x = if x & step != 0 { 1 } else { 0 };
+        let region_y = if y & step != 0 { 1 } else { 0 };
+        index += step * step * ((3 * region_x) ^ region_y);
+
+        // rotate the quadrant so the sub curve is connected
+        if region_y == 0 {
+            if region_x == 1 {
+                x = grid_size - 1 - x;
+                y = grid_size - 1 - y;
+            }
+            std::mem::swap(&mut x, &mut y);
+        }
+        step /= 2;
+    }
+
+    index
+}
+
+
+// One pass over one tile, handed back to the queue with finish_job once it is in the film.
+pub struct TileJob {
+    pub tile: Tile,
+    pub pass: u32,
+    tile_index: usize
+}
+
+// Work shared by all workers: every pass renders one sample for each tile.
+// Workers take the next job until none are left, so nobody idles while others still have work.
+pub struct TileQueue {
+    tiles: Vec<Tile>,
+    pass_count: u32,
+    next_job: AtomicUsize,
+    // passes of every tile that are in the film
+    finished_passes: Mutex<Vec<u32>>,
+    pass_finished: Condvar,
+    // set when a worker died with a job that others may be waiting for
+    is_aborted: AtomicBool
+}
+
+impl TileQueue {
+    pub fn new(tiles: Vec<Tile>, pass_count: u32) -> TileQueue {
+        let tile_count = tiles.len();
+        TileQueue {
+            tiles,
+            pass_count,
+            next_job: AtomicUsize::new(0),
+            finished_passes: Mutex::new(vec![0; tile_count]),
+            pass_finished: Condvar::new(),
+            is_aborted: AtomicBool::new(false)
+        }
+    }
+
+    // The next tile and the pass it belongs to, passes are handed out one after another.
+    // Blocks until the previous pass of the tile is finished, so every pixel gets its samples in order and
+    // the film adds them up the same way on every run. That only waits when there are fewer tiles than workers.
+    pub fn next_job(&self) -> Option<TileJob> {
+        let job = self.next_job.fetch_add(1, Ordering::Relaxed);
+        if job >= self.get_job_count() {
+            return None;
+        }
+
+        let pass = (job / self.tiles.len()) as u32;
+        let tile_index = job % self.tiles.len();
+        let mut finished_passes = self.finished_passes.lock().unwrap();
+        while finished_passes[tile_index] < pass {
+            if self.is_aborted.load(Ordering::Relaxed) {
+                return None;
+            }
+            finished_passes = self.pass_finished.wait(finished_passes).unwrap();
+        }
+
+        Some(TileJob { tile: self.tiles[tile_index], pass, tile_index })
+    }
+
+    // has to be called for every job once its samples are added to the film
+    pub fn finish_job(&self, job: &TileJob) {
+        self.finished_passes.lock().unwrap()[job.tile_index] = job.pass + 1;
+        self.pass_finished.notify_all();
+    }
+
+    // Wakes the workers waiting for a pass that will never be finished, next_job returns None to them.
+    pub fn abort(&self) {
+        let _finished_passes = self.finished_passes.lock().unwrap();
+        self.is_aborted.store(true, Ordering::Relaxed);
+        self.pass_finished.notify_all();
+    }
+
+    pub fn get_job_count(&self) -> usize {
+        self.tiles.len() * self.pass_count as usize
+    }
+
+    pub fn get_tiles(&self) -> &Vec<Tile> {
+        &self.tiles
+    }
+}
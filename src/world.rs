@@ -1,37 +1,129 @@
 
-use crate::object::{Hittable, HitRecord};
+use crate::object::{Hittable, HitRecord, ShapeSample};
+use crate::object::aabb::Aabb;
+use crate::object::bvh::Bvh;
 use crate::ray::Ray;
-use crate::math::vec3::Color;
+use crate::sampler::Sampler;
+use crate::math::vec3::{Vec3, Point3, Color};
 
 
-#[derive(Clone)]
 pub struct World {
-    objects: Vec<Box<dyn Hittable>>
+    objects: Vec<Box<dyn Hittable>>,
+    bvh: Option<Bvh>,
+    lights: Vec<usize>,
+    sky_color: Color
 }
 
 impl World {
     pub fn new_default() -> World {
-        World { objects: Vec::new() }
+        World {
+            objects: Vec::new(),
+            bvh: None,
+            lights: Vec::new(),
+            sky_color: Color::new(1.0, 1.0, 1.0)
+        }
     }
 
     pub fn new(object: Box<dyn Hittable>) -> World {
-        let mut objects = Vec::new();
-        objects.push(object);
-        World { objects } 
+        let mut world = World::new_default();
+        world.add_object(object);
+        world
+    }
+
+    pub fn world_hit(&self, ray: &Ray, weight_min: f64, weight_max: f64) -> Result<HitRecord<'_>, ()> {
+        match &self.bvh {
+            Some(bvh) => {
+                bvh.traverse(ray, weight_min, weight_max, |index, ray, weight_min, weight_max| {
+                    self.objects[index].hit(ray, weight_min, weight_max).map(|mut record| {
+                        record.object_id = index as u32 + 1;
+                        record
+                    })
+                })
+            }
+            None => {
+                self.linear_hit(ray, weight_min, weight_max)
+            }
+        }
+    }
+
+    // Has to be called after the last object is added, world_hit falls back to a linear search otherwise.
+    pub fn build_bvh(&mut self) {
+        let object_bounds: Vec<Aabb> = self.objects.iter()
+            .map(|object| object.bounding_box())
+            .collect();
+
+        self.bvh = Some(Bvh::new(&object_bounds));
+    }
+
+    // Collects the emissive objects, has to be called after the last object is added.
+    pub fn build_light_list(&mut self) {
+        self.lights = (0 .. self.objects.len())
+            .filter(|index| self.objects[*index].is_emissive())
+            .collect();
+    }
+
+    pub fn get_light_count(&self) -> usize {
+        self.lights.len()
+    }
+
+    // Picks one light uniformly and a point on it, the pdf includes the choice of the light.
+    pub fn sample_light(&self, origin: &Point3, sampler: &mut dyn Sampler) -> Option<ShapeSample> {
+        if self.lights.is_empty() {
+            return None;
+        }
+
+        let light_index = ((sampler.get_1d() * self.lights.len() as f64) as usize).min(self.lights.len() - 1);
+        let mut sample = self.objects[self.lights[light_index]].sample_point(origin, sampler)?;
+        sample.pdf /= self.lights.len() as f64;
+        Some(sample)
+    }
+
+    // pdf with which sample_light would have picked direction
+    pub fn light_pdf(&self, origin: &Point3, direction: &Vec3) -> f64 {
+        if self.lights.is_empty() {
+            return 0.0;
+        }
+
+        let pdf_sum: f64 = self.lights.iter()
+            .map(|index| self.objects[*index].pdf_value(origin, direction))
+            .sum();
+        pdf_sum / self.lights.len() as f64
+    }
+
+    pub fn add_object(&mut self, object: Box<dyn Hittable>) {
+        self.objects.push(object);
+        self.bvh = None;
+        self.lights.clear();
+    }
+
+    pub fn clear_all_objects(&mut self) {
+        self.objects.clear();
+        self.bvh = None;
+        self.lights.clear();
+    }
+
+    pub fn get_object_count(&self) -> usize {
+        self.objects.len()
+    }
+
+    pub fn get_sky_color(&self) -> Color {
+        self.sky_color
+    }
+
+    pub fn set_sky_color(&mut self, sky_color: Color) {
+        self.sky_color = sky_color;
     }
 
-    pub fn world_hit(&self, ray: &Ray, weight_min: f64, weight_max: f64) -> Result<HitRecord, ()> {
+    fn linear_hit(&self, ray: &Ray, weight_min: f64, weight_max: f64) -> Result<HitRecord<'_>, ()> {
         let mut closest_so_far = weight_max;
 
         let mut hit_record: Option<HitRecord> = None;
         for i in 0 .. self.objects.len() {
             let object = self.objects[i].as_ref();
-            match object.hit(ray, weight_min, closest_so_far) {
-                Ok(record) => {
-                    closest_so_far = record.weight;
-                    hit_record = Some(record);
-                }
-                Err(()) => {}
+            if let Ok(mut record) = object.hit(ray, weight_min, closest_so_far) {
+                record.object_id = i as u32 + 1;
+                closest_so_far = record.weight;
+                hit_record = Some(record);
             }
         }
 
@@ -45,17 +137,4 @@ impl World {
         }
     }
 
-    pub fn add_object(&mut self, object: Box<dyn Hittable>) {
-        self.objects.push(object);
-    }
-
-    pub fn clear_all_objects(&mut self) {
-        self.objects.clear();
-    }
-
-    pub fn get_sky_color(&self) -> Color {
-        Color::new(1.0, 1.0, 1.0)
-    }
-    
 }
-
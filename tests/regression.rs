@@ -0,0 +1,120 @@
+
+// Renders the scenes in tests/scenes without a window at a fixed seed and compares them with the images in
+// tests/references. Renders are deterministic, so the tolerance only has to absorb floating point differences
+// between platforms, a change to a material, the camera or the integrator shows up far above it.
+// After an intended change, run with UPDATE_REFERENCES=1 to render new references and check them in.
+
+use std::env;
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+use image::{Rgb, RgbImage};
+
+
+const IMAGE_SIZE: (u32, u32) = (64, 64);
+const SEED: u64 = 1;
+
+// root mean square difference of the 8-bit sRGB channels, scaled to [0, 1]
+const RMSE_TOLERANCE: f64 = 0.01;
+
+// differences are hard to see at their real size, the diff image scales them up
+const DIFF_GAIN: f64 = 8.0;
+
+
+#[test]
+fn dielectric() {
+    check_scene("dielectric");
+}
+
+#[test]
+fn metal() {
+    check_scene("metal");
+}
+
+#[test]
+fn conductor() {
+    check_scene("conductor");
+}
+
+#[test]
+fn camera() {
+    check_scene("camera");
+}
+
+#[test]
+fn cornell_box() {
+    check_scene("cornell_box");
+}
+
+
+fn check_scene(name: &str) {
+    let test_directory = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests");
+    let output_directory = Path::new(env!("CARGO_TARGET_TMPDIR")).join("regression");
+    fs::create_dir_all(&output_directory).unwrap();
+
+    let scene_path = test_directory.join("scenes").join(format!("{}.scene", name));
+    let reference_path = test_directory.join("references").join(format!("{}.png", name));
+    let render_path = output_directory.join(format!("{}.png", name));
+    render(&scene_path, &render_path);
+
+    if env::var_os("UPDATE_REFERENCES").is_some() {
+        fs::copy(&render_path, &reference_path).unwrap();
+        return;
+    }
+
+    let rendered = image::open(&render_path).unwrap().to_rgb8();
+    let reference = match image::open(&reference_path) {
+        Ok(reference) => reference.to_rgb8(),
+        Err(error) => panic!("missing reference {}: {}, run with UPDATE_REFERENCES=1 to create it", reference_path.display(), error)
+    };
+    assert_eq!(rendered.dimensions(), reference.dimensions(), "{} was rendered at a different size than its reference", name);
+
+    let rmse = get_rmse(&rendered, &reference);
+    if rmse > RMSE_TOLERANCE {
+        let diff_path = output_directory.join(format!("{}.diff.png", name));
+        make_diff_image(&rendered, &reference).save(&diff_path).unwrap();
+        panic!("{} differs from its reference by {:.4} rmse (tolerance {}), render: {}, diff: {}",
+            name, rmse, RMSE_TOLERANCE, render_path.display(), diff_path.display());
+    }
+}
+
+fn render(scene_path: &Path, output_path: &Path) {
+    let output = Command::new(env!("CARGO_BIN_EXE_raytracer-rs"))
+        .arg(scene_path)
+        .args(["--width", &IMAGE_SIZE.0.to_string(), "--height", &IMAGE_SIZE.1.to_string()])
+        .args(["--seed", &SEED.to_string()])
+        .args(["--threads", "2"])
+        .arg("--output")
+        .arg(output_path)
+        .output()
+        .unwrap();
+
+    assert!(output.status.success(), "rendering {} failed:\n{}", scene_path.display(), String::from_utf8_lossy(&output.stderr));
+}
+
+fn get_rmse(lhs: &RgbImage, rhs: &RgbImage) -> f64 {
+    let mut square_sum = 0.0;
+    for (lhs_pixel, rhs_pixel) in lhs.pixels().zip(rhs.pixels()) {
+        for channel in 0 .. 3 {
+            let difference = (lhs_pixel[channel] as f64 - rhs_pixel[channel] as f64) / 255.0;
+            square_sum += difference * difference;
+        }
+    }
+
+    let channel_count = (lhs.width() * lhs.height() * 3).max(1);
+    (square_sum / channel_count as f64).sqrt()
+}
+
+// Black where the images match, brighter the more a channel differs.
+fn make_diff_image(lhs: &RgbImage, rhs: &RgbImage) -> RgbImage {
+    RgbImage::from_fn(lhs.width(), lhs.height(), |x, y| {
+        let lhs_pixel = lhs.get_pixel(x, y);
+        let rhs_pixel = rhs.get_pixel(x, y);
+        let diff_channel = |channel: usize| {
+            let difference = (lhs_pixel[channel] as f64 - rhs_pixel[channel] as f64).abs();
+            (difference * DIFF_GAIN).min(255.0) as u8
+        };
+        Rgb([diff_channel(0), diff_channel(1), diff_channel(2)])
+    })
+}